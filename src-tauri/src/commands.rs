@@ -1,64 +1,53 @@
 use crate::database::Database;
-use crate::models::{TodoEvent, CreateEventRequest, UpdateEventRequest, EventFilter};
+use crate::config::{ConfigStore, DatabaseInfo};
+use crate::error::AppError;
+use crate::executor::{self, DbExecutor};
+use crate::ranking::{RankedTask, RankingWeights};
+use crate::models::{TodoEvent, CreateEventRequest, UpdateEventRequest, EventFilter, DeletePolicy, DeleteEventResult, CriticalPathReport, SearchResult, Page, PageRequest, SavedView, CreateViewRequest, UpdateViewRequest, HistoryEntry, UndoResult, UndoState, ChangeCause, DatabaseStatus, UpdateEventResult};
+use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
-use tauri::State;
-use serde::{Deserialize, Serialize};
-
-pub struct AppState {
-    pub db: Mutex<Database>,
-    pub current_db_path: Mutex<String>,
-}
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use chrono::{DateTime, Utc};
 
-pub type DbState = Mutex<Database>;
+pub type DbState = DbExecutor;
 pub type DbPathState = Mutex<String>;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DatabaseInfo {
-    pub path: String,
-    pub name: String,
-    pub last_modified: String,
-    pub size: String,
-}
+pub type ConfigState = ConfigStore;
+pub type StartupWarningState = Mutex<Option<String>>;
 
 #[tauri::command]
 pub async fn create_event(
     db: State<'_, DbState>,
     request: CreateEventRequest,
-) -> Result<TodoEvent, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    db.create_event(request)
-        .map_err(|e| format!("Failed to create event: {}", e))
+) -> Result<TodoEvent, AppError> {
+    db.write(move |db| db.create_event(request)).await
 }
 
 #[tauri::command]
 pub async fn get_event(
     db: State<'_, DbState>,
     id: String,
-) -> Result<Option<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    db.get_event(&id)
-        .map_err(|e| format!("Failed to get event: {}", e))
+) -> Result<Option<TodoEvent>, AppError> {
+    db.read(move |db| db.get_event(&id)).await
 }
 
 #[tauri::command]
 pub async fn get_all_events(
     db: State<'_, DbState>,
-) -> Result<Vec<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    db.get_all_events()
-        .map_err(|e| format!("Failed to get events: {}", e))
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<Page<TodoEvent>, AppError> {
+    db.read(move |db| db.filter_events(EventFilter::default(), PageRequest { limit, cursor })).await
 }
 
 #[tauri::command]
 pub async fn update_event(
     db: State<'_, DbState>,
     request: UpdateEventRequest,
-) -> Result<Option<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    db.update_event(request)
-        .map_err(|e| format!("Failed to update event: {}", e))
+) -> Result<Option<UpdateEventResult>, AppError> {
+    db.write(move |db| db.update_event(request)).await
 }
 
 #[tauri::command]
@@ -66,18 +55,9 @@ pub async fn update_event_status(
     db: State<'_, DbState>,
     id: String,
     status: crate::models::EventStatus,
-) -> Result<Vec<TodoEvent>, String> {
+) -> Result<Vec<TodoEvent>, AppError> {
     println!("🦀 Rust update_event_status command called with ID: {}, status: {:?}", id, status);
-    let db = db.lock().map_err(|e| {
-        println!("🦀 Database lock error: {}", e);
-        format!("Database lock error: {}", e)
-    })?;
-    
-    let result = db.update_event_status_cascade(&id, status)
-        .map_err(|e| {
-            println!("🦀 Failed to update event status: {}", e);
-            format!("Failed to update event status: {}", e)
-        });
+    let result = db.write(move |db| db.update_event_status_cascade(&id, status)).await;
     
     match &result {
         Ok(events) => println!("🦀 Update event status result: {} events updated", events.len()),
@@ -91,71 +71,238 @@ pub async fn update_event_status(
 pub async fn delete_event(
     db: State<'_, DbState>,
     id: String,
-) -> Result<bool, String> {
+    policy: Option<DeletePolicy>,
+) -> Result<DeleteEventResult, AppError> {
     println!("🦀 Rust delete_event command called with ID: {}", id);
-    let db = db.lock().map_err(|e| {
-        println!("🦀 Database lock error: {}", e);
-        format!("Database lock error: {}", e)
-    })?;
-    
-    let result = db.delete_event(&id)
-        .map_err(|e| {
-            println!("🦀 Failed to delete event: {}", e);
-            format!("Failed to delete event: {}", e)
-        });
+    let result = db.write(move |db| db.delete_event(&id, policy.unwrap_or_default())).await;
     
     match &result {
-        Ok(success) => println!("🦀 Delete event result: {}", success),
+        Ok(result) => println!("🦀 Delete event result: {} deleted, {} updated", result.deleted.len(), result.updated.len()),
         Err(error) => println!("🦀 Delete event error: {}", error),
     }
     
     result
 }
 
+#[tauri::command]
+pub async fn list_trash(
+    db: State<'_, DbState>,
+) -> Result<Vec<TodoEvent>, AppError> {
+    db.read(move |db| db.list_trash()).await
+}
+
+#[tauri::command]
+pub async fn restore_event(
+    db: State<'_, DbState>,
+    id: String,
+) -> Result<Vec<TodoEvent>, AppError> {
+    db.write(move |db| db.restore_event(&id)).await
+}
+
+#[tauri::command]
+pub async fn purge_trash(
+    db: State<'_, DbState>,
+) -> Result<Vec<String>, AppError> {
+    db.write(move |db| db.purge_trash(None, ChangeCause::User)).await
+}
+
+#[tauri::command]
+pub async fn get_trash_retention_days(
+    db: State<'_, DbState>,
+) -> Result<i64, AppError> {
+    db.read(move |db| db.trash_retention_days()).await
+}
+
+#[tauri::command]
+pub async fn set_trash_retention_days(
+    db: State<'_, DbState>,
+    days: i64,
+) -> Result<(), AppError> {
+    db.write(move |db| db.set_trash_retention_days(days)).await
+}
+
+#[tauri::command]
+pub async fn archive_completed(
+    db: State<'_, DbState>,
+    older_than: Option<DateTime<Utc>>,
+) -> Result<Vec<String>, AppError> {
+    db.write(move |db| db.archive_completed(older_than)).await
+}
+
+#[tauri::command]
+pub async fn get_auto_archive_days(
+    db: State<'_, DbState>,
+) -> Result<Option<i64>, AppError> {
+    db.read(move |db| db.auto_archive_days()).await
+}
+
+#[tauri::command]
+pub async fn set_auto_archive_days(
+    db: State<'_, DbState>,
+    days: Option<i64>,
+) -> Result<(), AppError> {
+    db.write(move |db| db.set_auto_archive_days(days)).await
+}
+
 #[tauri::command]
 pub async fn filter_events(
     db: State<'_, DbState>,
     filter: EventFilter,
-) -> Result<Vec<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    db.filter_events(filter)
-        .map_err(|e| format!("Failed to filter events: {}", e))
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<Page<TodoEvent>, AppError> {
+    db.read(move |db| db.filter_events(filter, PageRequest { limit, cursor })).await
+}
+
+#[tauri::command]
+pub async fn query_events(
+    db: State<'_, DbState>,
+    query: String,
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<Page<TodoEvent>, AppError> {
+    let filter = EventFilter {
+        query: Some(query),
+        ..EventFilter::default()
+    };
+    db.read(move |db| db.filter_events(filter, PageRequest { limit, cursor })).await
+}
+
+#[tauri::command]
+pub async fn search_events(
+    db: State<'_, DbState>,
+    query: String,
+    limit: Option<usize>,
+    include_archived: Option<bool>,
+) -> Result<Vec<SearchResult>, AppError> {
+    db.read(move |db| db.search_events(&query, limit.unwrap_or(50), include_archived.unwrap_or(false))).await
 }
 
 #[tauri::command]
 pub async fn get_event_dependencies(
     db: State<'_, DbState>,
     id: String,
-) -> Result<Vec<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    
-    if let Some(event) = db.get_event(&id).map_err(|e| format!("Failed to get event: {}", e))? {
-        let mut dependencies = Vec::new();
-        for dep_id in event.dependencies {
-            if let Some(dep_event) = db.get_event(&dep_id).map_err(|e| format!("Failed to get dependency: {}", e))? {
-                dependencies.push(dep_event);
-            }
+) -> Result<Vec<TodoEvent>, AppError> {
+    db.read(move |db| {
+        if db.get_event(&id)?.is_none() {
+            return Err(AppError::not_found("event", id));
         }
-        Ok(dependencies)
-    } else {
-        Err("Event not found".to_string())
-    }
+        db.get_dependencies(&id)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_event_dependents(
     db: State<'_, DbState>,
     id: String,
-) -> Result<Vec<TodoEvent>, String> {
-    let db = db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    
-    let all_events = db.get_all_events().map_err(|e| format!("Failed to get events: {}", e))?;
-    let dependents: Vec<TodoEvent> = all_events
-        .into_iter()
-        .filter(|event| event.dependencies.contains(&id))
-        .collect();
-    
-    Ok(dependents)
+) -> Result<Vec<TodoEvent>, AppError> {
+    db.read(move |db| {
+        if db.get_event(&id)?.is_none() {
+            return Err(AppError::not_found("event", id));
+        }
+        db.get_dependents(&id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_event_history(
+    db: State<'_, DbState>,
+    id: String,
+) -> Result<Vec<HistoryEntry>, AppError> {
+    db.read(move |db| db.get_event_history(&id)).await
+}
+
+#[tauri::command]
+pub async fn get_critical_path(
+    db: State<'_, DbState>,
+    target_id: Option<String>,
+) -> Result<CriticalPathReport, AppError> {
+    db.read(move |db| {
+        db.critical_path(target_id.as_deref())?
+            .ok_or_else(|| AppError::not_found("event", target_id.unwrap_or_default()))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn next_tasks(
+    db: State<'_, DbState>,
+    limit: Option<usize>,
+    weights: Option<RankingWeights>,
+) -> Result<Vec<RankedTask>, AppError> {
+    db.read(move |db| db.next_tasks(limit, &weights.unwrap_or_default())).await
+}
+
+// Undo/redo commands
+
+#[tauri::command]
+pub async fn undo(
+    db: State<'_, DbState>,
+) -> Result<Option<UndoResult>, AppError> {
+    db.write(move |db| db.undo()).await
+}
+
+#[tauri::command]
+pub async fn redo(
+    db: State<'_, DbState>,
+) -> Result<Option<UndoResult>, AppError> {
+    db.write(move |db| db.redo()).await
+}
+
+#[tauri::command]
+pub async fn get_undo_state(
+    db: State<'_, DbState>,
+) -> Result<UndoState, AppError> {
+    db.read(move |db| db.undo_state()).await
+}
+
+// Saved view commands
+
+#[tauri::command]
+pub async fn create_view(
+    db: State<'_, DbState>,
+    request: CreateViewRequest,
+) -> Result<SavedView, AppError> {
+    db.write(move |db| db.create_view(request)).await
+}
+
+#[tauri::command]
+pub async fn get_views(
+    db: State<'_, DbState>,
+) -> Result<Vec<SavedView>, AppError> {
+    db.read(move |db| db.get_views()).await
+}
+
+#[tauri::command]
+pub async fn update_view(
+    db: State<'_, DbState>,
+    request: UpdateViewRequest,
+) -> Result<Option<SavedView>, AppError> {
+    db.write(move |db| db.update_view(request)).await
+}
+
+#[tauri::command]
+pub async fn delete_view(
+    db: State<'_, DbState>,
+    id: String,
+) -> Result<bool, AppError> {
+    db.write(move |db| db.delete_view(&id)).await
+}
+
+#[tauri::command]
+pub async fn execute_view(
+    db: State<'_, DbState>,
+    id: String,
+    limit: Option<usize>,
+    cursor: Option<String>,
+) -> Result<Page<TodoEvent>, AppError> {
+    db.read(move |db| {
+        db.execute_view(&id, PageRequest { limit, cursor })?
+            .ok_or_else(|| AppError::not_found("view", id))
+    })
+    .await
 }
 
 // Database management commands
@@ -163,48 +310,114 @@ pub async fn get_event_dependents(
 #[tauri::command]
 pub async fn get_current_database_path(
     db_path: State<'_, DbPathState>,
-) -> Result<String, String> {
-    let path = db_path.lock().map_err(|e| format!("Path lock error: {}", e))?;
+) -> Result<String, AppError> {
+    let path = db_path.lock()?;
     Ok(path.clone())
 }
 
 #[tauri::command]
-pub async fn get_recent_databases() -> Result<Vec<DatabaseInfo>, String> {
-    // For now, return an empty list. In a real implementation,
-    // you'd read from a configuration file or registry
-    Ok(vec![])
+pub async fn get_database_status(
+    db: State<'_, DbState>,
+) -> Result<DatabaseStatus, AppError> {
+    db.status()
+}
+
+// Why the database restored at startup could not be opened; returned once
+#[tauri::command]
+pub async fn take_startup_warning(
+    warning: State<'_, StartupWarningState>,
+) -> Result<Option<String>, AppError> {
+    Ok(warning.lock()?.take())
+}
+
+#[tauri::command]
+pub async fn get_recent_databases(
+    config: State<'_, ConfigState>,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    config.recent_databases()
+}
+
+#[tauri::command]
+pub async fn set_recent_database_pinned(
+    config: State<'_, ConfigState>,
+    path: String,
+    pinned: bool,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    config.set_pinned(&path, pinned)?;
+    config.recent_databases()
+}
+
+#[tauri::command]
+pub async fn remove_recent_database(
+    config: State<'_, ConfigState>,
+    path: String,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    config.remove_database(&path)?;
+    config.recent_databases()
+}
+
+// Drop entries whose file no longer exists (pinned entries are kept)
+#[tauri::command]
+pub async fn prune_recent_databases(
+    config: State<'_, ConfigState>,
+) -> Result<Vec<DatabaseInfo>, AppError> {
+    config.prune_missing()?;
+    config.recent_databases()
 }
 
+// Create an empty database. An existing file is only replaced when `overwrite` is set,
+// i.e. the user confirmed it in the save dialog
 #[tauri::command]
-pub async fn create_new_database(path: String) -> Result<(), String> {
+pub async fn create_new_database(
+    config: State<'_, ConfigState>,
+    db_path: State<'_, DbPathState>,
+    path: String,
+    overwrite: Option<bool>,
+) -> Result<(), AppError> {
     println!("Creating new database at: {}", path);
     
     // Ensure the directory exists
     if let Some(parent) = Path::new(&path).parent() {
         fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+            .map_err(|e| AppError::io(parent.display().to_string(), e))?;
+    }
+    
+    if Path::new(&path).exists() {
+        if !overwrite.unwrap_or(false) {
+            return Err(AppError::conflict(format!("{} already exists", path), vec![path]));
+        }
+        // Never replace the database that is open right now
+        let current_path = db_path.lock()?.clone();
+        if same_file(Path::new(&path), Path::new(&current_path)) {
+            return Err(AppError::conflict(
+                format!("{} is the database currently in use and cannot be replaced", path),
+                vec![path],
+            ));
+        }
     }
     
-    // Create a new database instance to initialize the file
-    Database::new(&path)
-        .map_err(|e| format!("Failed to create database: {}", e))?;
+    // Create the database next to the target and move it into place only once it is complete,
+    // so a failure leaves any existing file untouched
+    let new_path = path.clone();
+    executor::blocking(move || replace_with_new_database(Path::new(&new_path))).await?;
+    config.record_database(&path)?;
     
     println!("Database created successfully at: {}", path);
     Ok(())
 }
 
 #[tauri::command]
-pub async fn validate_database(path: String) -> Result<(), String> {
+pub async fn validate_database(path: String) -> Result<(), AppError> {
     println!("Validating database at: {}", path);
     
     // Check if file exists
     if !Path::new(&path).exists() {
-        return Err("Database file does not exist".to_string());
+        return Err(AppError::not_found("database", path));
     }
     
-    // Try to open the database to validate it
-    Database::new(&path)
-        .map_err(|e| format!("Invalid database file: {}", e))?;
+    // Open read-only and check the schema version without migrating
+    let checked_path = path.clone();
+    executor::blocking(move || Database::validate(&checked_path)).await?;
     
     println!("Database validation successful: {}", path);
     Ok(())
@@ -214,29 +427,258 @@ pub async fn validate_database(path: String) -> Result<(), String> {
 pub async fn switch_database(
     db: State<'_, DbState>,
     db_path: State<'_, DbPathState>,
+    config: State<'_, ConfigState>,
     path: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     println!("Switching to database: {}", path);
     
     // Validate the new database first
     validate_database(path.clone()).await?;
     
-    // Create new database connection
-    let new_db = Database::new(&path)
-        .map_err(|e| format!("Failed to open database: {}", e))?;
-    
-    // Replace the current database connection
-    let mut db_guard = db.lock()
-        .map_err(|e| format!("Database lock error: {}", e))?;
-    *db_guard = new_db;
+    // Open the new database and replace the current connections
+    db.switch(path.clone()).await?;
     
     // Update the current database path
-    let mut path_guard = db_path.lock()
-        .map_err(|e| format!("Path lock error: {}", e))?;
+    let mut path_guard = db_path.lock()?;
     *path_guard = path.clone();
+    drop(path_guard);
+    
+    // Reopen this database on the next launch
+    config.set_active_database(&path)?;
+    config.record_database(&path)?;
     
     println!("Database switched successfully to: {}", path);
     Ok(())
 }
 
-// Remove the custom dialog commands since we'll use the plugin properly
\ No newline at end of file
+// File type filter shown in the native file dialogs
+#[derive(Debug, Clone, Deserialize)]
+pub struct DialogFilter {
+    pub name: String,
+    pub extensions: Vec<String>,
+}
+
+// Location chosen in the save dialog; `overwrite` is set when the file already exists
+// and the user confirmed replacing it in the native dialog. `exists` without `overwrite`
+// means the caller still has to ask before passing `overwrite` to `create_new_database`
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveDialogSelection {
+    pub path: String,
+    pub overwrite: bool,
+    pub exists: bool,
+}
+
+// Ask where to create a new database. Returns None when the dialog is cancelled.
+// Nothing is written here; pass the selection on to `create_new_database`
+#[tauri::command]
+pub async fn show_save_dialog(
+    app: AppHandle,
+    title: String,
+    default_path: Option<String>,
+    filters: Option<Vec<DialogFilter>>,
+) -> Result<Option<SaveDialogSelection>, AppError> {
+    let (directory, file_name) = dialog_location(&app, default_path)?;
+    let file_name = file_name.unwrap_or_else(|| "todo-database.db".to_string());
+    
+    let dialog_app = app.clone();
+    let selected = executor::blocking(move || {
+        let mut dialog = dialog_app.dialog().file().set_title(title).set_directory(&directory).set_file_name(file_name);
+        for filter in dialog_filters(filters) {
+            let extensions: Vec<&str> = filter.extensions.iter().map(String::as_str).collect();
+            dialog = dialog.add_filter(filter.name, &extensions);
+        }
+        dialog.blocking_save_file().map(dialog_path).transpose()
+    })
+    .await?;
+    
+    let Some(mut path) = selected else {
+        return Ok(None);
+    };
+    // The native dialog already asked before returning an existing file, but not for the
+    // name with the extension added here; that one is left for the caller to confirm
+    let mut overwrite = path.exists();
+    if path.extension().is_none() {
+        path.set_extension("db");
+        overwrite = false;
+    }
+    let exists = path.exists();
+    
+    Ok(Some(SaveDialogSelection { path: path.to_string_lossy().to_string(), overwrite, exists }))
+}
+
+// Ask for an existing database. The selected file is validated before it is returned
+#[tauri::command]
+pub async fn show_open_dialog(
+    app: AppHandle,
+    title: String,
+    default_path: Option<String>,
+    filters: Option<Vec<DialogFilter>>,
+) -> Result<Option<String>, AppError> {
+    let (directory, file_name) = dialog_location(&app, default_path)?;
+    
+    let dialog_app = app.clone();
+    let selected = executor::blocking(move || {
+        let mut dialog = dialog_app.dialog().file().set_title(title).set_directory(&directory);
+        if let Some(file_name) = file_name {
+            dialog = dialog.set_file_name(file_name);
+        }
+        for filter in dialog_filters(filters) {
+            let extensions: Vec<&str> = filter.extensions.iter().map(String::as_str).collect();
+            dialog = dialog.add_filter(filter.name, &extensions);
+        }
+        dialog.blocking_pick_file().map(dialog_path).transpose()
+    })
+    .await?;
+    
+    let Some(path) = selected else {
+        return Ok(None);
+    };
+    let path = path.to_string_lossy().to_string();
+    validate_database(path.clone()).await?;
+    
+    Ok(Some(path))
+}
+
+// Split the requested default path into the folder the dialog opens in and a
+// suggested file name. Dialogs start in the databases folder under the app data
+// directory unless the default path names another folder
+fn dialog_location(app: &AppHandle, default_path: Option<String>) -> Result<(PathBuf, Option<String>), AppError> {
+    let Some(default_path) = default_path.map(PathBuf::from) else {
+        return Ok((default_dialog_directory(app)?, None));
+    };
+    if default_path.is_dir() {
+        return Ok((default_path, None));
+    }
+    let file_name = default_path.file_name().map(|name| name.to_string_lossy().to_string());
+    let directory = match default_path.parent() {
+        Some(parent) if parent.is_dir() => parent.to_path_buf(),
+        _ => default_dialog_directory(app)?,
+    };
+    Ok((directory, file_name))
+}
+
+fn default_dialog_directory(app: &AppHandle) -> Result<PathBuf, AppError> {
+    let directory = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Io { message: e.to_string(), path: None })?
+        .join("databases");
+    fs::create_dir_all(&directory).map_err(|e| AppError::io(directory.display().to_string(), e))?;
+    Ok(directory)
+}
+
+fn dialog_filters(filters: Option<Vec<DialogFilter>>) -> Vec<DialogFilter> {
+    match filters {
+        Some(filters) if !filters.is_empty() => filters,
+        _ => vec![DialogFilter { name: "Database Files".to_string(), extensions: vec!["db".to_string()] }],
+    }
+}
+
+fn dialog_path(path: tauri_plugin_dialog::FilePath) -> Result<PathBuf, AppError> {
+    path.into_path().map_err(|e| AppError::Io { message: e.to_string(), path: None })
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Build a new database in a temporary file and rename it over `path`.
+// A database still in use by another instance is never replaced
+fn replace_with_new_database(path: &Path) -> Result<(), AppError> {
+    if path.exists() {
+        if let Some(holder) = Database::instance_lock_holder(path)? {
+            return Err(AppError::conflict(
+                format!(
+                    "{} is open in another instance ({}, pid {}) and cannot be replaced",
+                    path.display(), holder.host, holder.pid
+                ),
+                vec![holder.instance_id],
+            ));
+        }
+    }
+    
+    let temp_path = sidecar_path(path, ".new");
+    for file in [temp_path.clone(), sidecar_path(&temp_path, "-wal"), sidecar_path(&temp_path, "-shm")] {
+        remove_if_exists(&file)?;
+    }
+    // Closing the only connection checkpoints and removes the temporary WAL file
+    if let Err(e) = Database::new(&temp_path) {
+        let _ = remove_if_exists(&temp_path);
+        return Err(e);
+    }
+    
+    // A leftover WAL from the old file would be replayed into the new one
+    for suffix in ["-wal", "-shm"] {
+        remove_if_exists(&sidecar_path(path, suffix))?;
+    }
+    fs::rename(&temp_path, path).map_err(|e| AppError::io(path.display().to_string(), e))
+}
+
+// `path` with `suffix` appended to the file name, e.g. the `-wal` file of a database
+fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(suffix);
+    PathBuf::from(sidecar)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), AppError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::io(path.display().to_string(), e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_database(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todo-scheduler-commands-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        for file in [path.clone(), sidecar_path(&path, "-wal"), sidecar_path(&path, "-shm")] {
+            remove_if_exists(&file).unwrap();
+        }
+        path
+    }
+
+    fn create_event(db: &Database) {
+        db.create_event(CreateEventRequest {
+            name: "A".to_string(),
+            description: String::new(),
+            tags: HashMap::new(),
+            dependencies: Vec::new(),
+            due_at: None,
+            start_after: None,
+            estimate_minutes: None,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn replacing_a_database_leaves_an_empty_one() {
+        let path = temp_database("replace.db");
+        create_event(&Database::new(&path).unwrap());
+
+        replace_with_new_database(&path).unwrap();
+        let db = Database::new(&path).unwrap();
+        assert!(db.get_all_events().unwrap().is_empty());
+        assert!(!sidecar_path(&path, ".new").exists());
+    }
+
+    #[test]
+    fn database_in_use_by_another_instance_is_not_replaced() {
+        let path = temp_database("in-use.db");
+        let db = Database::new(&path).unwrap();
+        create_event(&db);
+        assert!(db.acquire_instance_lock("other", "host", 1, Utc::now()).unwrap().is_none());
+
+        let error = replace_with_new_database(&path).unwrap_err();
+        assert_eq!(error.code(), "Conflict");
+        assert_eq!(db.get_all_events().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::error::{AppError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// 最近使用列表中最多保留的未固定数据库数量
+const RECENT_DATABASE_LIMIT: usize = 10;
+
+// 应用配置，以 JSON 保存在应用数据目录下的 config.json 中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppConfig {
+    // 上次使用的数据库，启动时重新打开
+    #[serde(default)]
+    pub active_database: Option<String>,
+    #[serde(default)]
+    pub recent_databases: Vec<RecentDatabase>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentDatabase {
+    pub path: String,
+    pub name: String,
+    pub last_opened: DateTime<Utc>,
+    pub size: u64,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+// 返回给界面的最近使用数据库，文件信息在查询时重新读取；文件不存在时 missing 为 true
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseInfo {
+    pub path: String,
+    pub name: String,
+    pub last_opened: DateTime<Utc>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub size: u64,
+    pub pinned: bool,
+    pub missing: bool,
+}
+
+pub struct ConfigStore {
+    path: PathBuf,
+    config: Mutex<AppConfig>,
+}
+
+impl ConfigStore {
+    // 读取配置文件；文件不存在或无法解析时使用默认配置
+    pub fn load(path: PathBuf) -> Self {
+        let config = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("⚠️ Ignoring invalid config file {}: {}", path.display(), e);
+                AppConfig::default()
+            }),
+            Err(_) => AppConfig::default(),
+        };
+        ConfigStore { path, config: Mutex::new(config) }
+    }
+
+    pub fn active_database(&self) -> Result<Option<String>> {
+        Ok(self.config.lock()?.active_database.clone())
+    }
+
+    pub fn set_active_database(&self, db_path: &str) -> Result<()> {
+        let path = normalize_path(db_path);
+        self.update(|config| config.active_database = Some(path))
+    }
+
+    // 把打开的数据库移到最近使用列表的最前面
+    pub fn record_database(&self, db_path: &str) -> Result<()> {
+        let path = normalize_path(db_path);
+        let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+        self.update(|config| {
+            let pinned = config.recent_databases.iter().any(|entry| entry.path == path && entry.pinned);
+            config.recent_databases.retain(|entry| entry.path != path);
+            config.recent_databases.insert(0, RecentDatabase {
+                name: display_name(&path),
+                path,
+                last_opened: Utc::now(),
+                size,
+                pinned,
+            });
+            // 固定的数据库不计入数量限制
+            let mut unpinned = 0;
+            config.recent_databases.retain(|entry| {
+                if entry.pinned {
+                    return true;
+                }
+                unpinned += 1;
+                unpinned <= RECENT_DATABASE_LIMIT
+            });
+        })
+    }
+
+    pub fn set_pinned(&self, db_path: &str, pinned: bool) -> Result<()> {
+        let path = normalize_path(db_path);
+        self.update(|config| {
+            for entry in config.recent_databases.iter_mut().filter(|entry| entry.path == path) {
+                entry.pinned = pinned;
+            }
+        })
+    }
+
+    pub fn remove_database(&self, db_path: &str) -> Result<()> {
+        let path = normalize_path(db_path);
+        self.update(|config| config.recent_databases.retain(|entry| entry.path != path))
+    }
+
+    // 移除文件已不存在且没有固定的数据库
+    pub fn prune_missing(&self) -> Result<()> {
+        self.update(|config| {
+            config.recent_databases.retain(|entry| entry.pinned || Path::new(&entry.path).exists())
+        })
+    }
+
+    // 固定的数据库在前，其余按最近打开时间排序
+    pub fn recent_databases(&self) -> Result<Vec<DatabaseInfo>> {
+        let config = self.config.lock()?;
+        let mut databases: Vec<DatabaseInfo> = config.recent_databases.iter().map(database_info).collect();
+        databases.sort_by(|a, b| b.pinned.cmp(&a.pinned).then(b.last_opened.cmp(&a.last_opened)));
+        Ok(databases)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut AppConfig)) -> Result<()> {
+        let mut config = self.config.lock()?;
+        f(&mut config);
+        self.save(&config)
+    }
+
+    // 先写入临时文件再替换，避免写入中断时损坏配置
+    fn save(&self, config: &AppConfig) -> Result<()> {
+        let content = serde_json::to_string_pretty(config)
+            .map_err(|e| AppError::Internal { message: e.to_string() })?;
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, content).map_err(|e| AppError::io(temp_path.display().to_string(), e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| AppError::io(self.path.display().to_string(), e))?;
+        Ok(())
+    }
+}
+
+// 命令行参数 --db <path> 或 --db=<path> 指定要打开的数据库
+pub fn database_arg(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--db" {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix("--db=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+fn database_info(entry: &RecentDatabase) -> DatabaseInfo {
+    let metadata = fs::metadata(&entry.path).ok();
+    DatabaseInfo {
+        path: entry.path.clone(),
+        name: entry.name.clone(),
+        last_opened: entry.last_opened,
+        last_modified: metadata
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .map(DateTime::<Utc>::from),
+        size: metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(entry.size),
+        pinned: entry.pinned,
+        missing: metadata.is_none(),
+    }
+}
+
+// 相对路径转为绝对路径，同一个文件只保留一条记录
+fn normalize_path(path: &str) -> String {
+    std::path::absolute(path)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+fn display_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todo-scheduler-config-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn db_path(dir: &Path, name: &str) -> String {
+        dir.join(format!("{}.db", name)).to_string_lossy().to_string()
+    }
+
+    fn names(store: &ConfigStore) -> Vec<String> {
+        store.recent_databases().unwrap().into_iter().map(|info| info.name).collect()
+    }
+
+    #[test]
+    fn keeps_ten_unpinned_databases_besides_pinned_ones() {
+        let dir = temp_dir("limit");
+        let store = ConfigStore::load(dir.join("config.json"));
+        store.record_database(&db_path(&dir, "db0")).unwrap();
+        store.set_pinned(&db_path(&dir, "db0"), true).unwrap();
+        for i in 1..=12 {
+            store.record_database(&db_path(&dir, &format!("db{}", i))).unwrap();
+        }
+
+        let expected: Vec<String> =
+            std::iter::once(0).chain((3..=12).rev()).map(|i| format!("db{}", i)).collect();
+        assert_eq!(names(&store), expected);
+
+        // 重新打开时移到最前面并保留固定状态
+        store.record_database(&db_path(&dir, "db0")).unwrap();
+        store.record_database(&db_path(&dir, "db5")).unwrap();
+        let databases = store.recent_databases().unwrap();
+        assert_eq!(databases.len(), 11);
+        assert!(databases[0].pinned && databases[0].name == "db0");
+        assert_eq!(databases[1].name, "db5");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prunes_missing_databases_unless_pinned() {
+        let dir = temp_dir("prune");
+        let store = ConfigStore::load(dir.join("config.json"));
+        fs::write(dir.join("present.db"), "").unwrap();
+        for name in ["present", "gone", "kept"] {
+            store.record_database(&db_path(&dir, name)).unwrap();
+        }
+        store.set_pinned(&db_path(&dir, "kept"), true).unwrap();
+
+        store.prune_missing().unwrap();
+        let databases = store.recent_databases().unwrap();
+        let summary: Vec<(&str, bool)> = databases.iter().map(|info| (info.name.as_str(), info.missing)).collect();
+        assert_eq!(summary, [("kept", true), ("present", false)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reads_the_database_argument() {
+        let args = |args: &[&str]| database_arg(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&["app", "--db", "a.db"]), Some("a.db".to_string()));
+        assert_eq!(args(&["app", "--db=b.db", "--db", "c.db"]), Some("b.db".to_string()));
+        assert_eq!(args(&["app", "--db="]), Some(String::new()));
+        assert_eq!(args(&["app", "--db"]), None);
+        assert_eq!(args(&["app", "--dbx", "a.db"]), None);
+        assert_eq!(args(&["app"]), None);
+    }
+
+    #[test]
+    fn saves_through_a_temporary_file() {
+        let dir = temp_dir("save");
+        let config_path = dir.join("config.json");
+        let store = ConfigStore::load(config_path.clone());
+        store.set_active_database(&db_path(&dir, "first")).unwrap();
+        assert!(!config_path.with_extension("json.tmp").exists());
+        let reloaded = ConfigStore::load(config_path.clone());
+        assert_eq!(reloaded.active_database().unwrap(), Some(db_path(&dir, "first")));
+
+        // 临时文件无法写入时，原来的配置保持不变
+        let saved = fs::read_to_string(&config_path).unwrap();
+        fs::create_dir(config_path.with_extension("json.tmp")).unwrap();
+        let error = store.set_active_database(&db_path(&dir, "second")).unwrap_err();
+        assert_eq!(error.code(), "Io");
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), saved);
+
+        // 无法解析的配置文件按默认配置处理
+        fs::write(&config_path, "{").unwrap();
+        assert_eq!(ConfigStore::load(config_path).active_database().unwrap(), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,225 +1,816 @@
-use crate::models::{TodoEvent, EventStatus, CreateEventRequest, UpdateEventRequest, EventFilter};
-use rusqlite::{Connection, Result, params};
+use crate::models::{TodoEvent, EventStatus, CreateEventRequest, UpdateEventRequest, UpdateEventResult, EventFilter, DeletePolicy, DeleteEventResult, CriticalPathReport, SearchResult, Page, PageRequest, SavedView, CreateViewRequest, UpdateViewRequest, ChangeCause, HistoryAction, HistoryEntry, EventSnapshot, UndoResult, UndoState, InstanceLock};
+use crate::error::{AppError, Result};
+use crate::filters;
+use crate::graph;
+use crate::history;
+use crate::migrations;
+use crate::ranking::{self, RankedTask, RankingWeights};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Row, params, params_from_iter};
+use rusqlite::types::Value;
+use rusqlite::ErrorCode;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
+use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json;
 
+// 事件查询使用的列，依赖关系从 event_dependencies 表聚合为 JSON 数组（保持插入顺序）。
+// 回收站中的前置事件不计入未删除事件的依赖
+const EVENT_COLUMNS: &str = "e.id, e.name, e.description, e.tags, e.status, e.created_at, e.updated_at,
+    (SELECT json_group_array(depends_on_id) FROM (
+        SELECT d.depends_on_id FROM event_dependencies d JOIN events dep ON dep.id = d.depends_on_id
+        WHERE d.event_id = e.id AND (e.deleted_at IS NOT NULL OR dep.deleted_at IS NULL)
+        ORDER BY d.rowid
+    )) AS dependencies,
+    e.due_at, e.start_after, e.completed_at, e.estimate_minutes, e.deleted_at, e.archived_at";
+const EVENT_COLUMN_COUNT: usize = 14;
+
+// 回收站中的事件默认保留的天数
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+// 保留期限和自动归档天数的上限（约 100 年）
+const MAX_SETTING_DAYS: i64 = 36500;
+
+// 操作日志最多保留的操作数量
+const JOURNAL_LIMIT: i64 = 200;
+
+// 数据库被其他连接锁定时等待的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 超过这个时间没有更新心跳的实例视为已经退出，其他实例可以接管写锁
+const INSTANCE_LOCK_TIMEOUT_SECS: i64 = 180;
+
 pub struct Database {
     conn: Connection,
+    // 正在记录的操作涉及的事件及其操作前的状态，不在可撤销操作中时为 None
+    journal: RefCell<Option<Vec<EventSnapshot>>>,
 }
 
 impl Database {
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        let db = Database { conn };
-        db.init_tables()?;
+        let db = Self::open(db_path)?;
+        db.migrate()?;
         Ok(db)
     }
 
-    fn init_tables(&self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS events (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                description TEXT NOT NULL,
-                tags TEXT NOT NULL,
-                status TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                dependencies TEXT NOT NULL
-            )",
-            [],
+    // 打开写连接但不升级结构。与其他实例共用文件时，只有获取写锁后才能执行迁移
+    pub fn open<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let network = is_network_path(db_path.as_ref());
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        // WAL 模式下读连接可以与写连接并发执行（内存数据库会保持 memory 模式）。
+        // WAL 依赖共享内存，不能用于网络共享上的文件，这时使用默认的回滚日志
+        let journal_mode = if network { "DELETE" } else { "WAL" };
+        conn.pragma_update_and_check(None, "journal_mode", journal_mode, |row| row.get::<_, String>(0))?;
+        migrations::check_version(&conn)?;
+        Ok(Database { conn, journal: RefCell::new(None) })
+    }
+
+    // 升级到最新的结构版本，返回是否执行了迁移
+    pub fn migrate(&self) -> Result<bool> {
+        let version = migrations::check_version(&self.conn)?;
+        migrations::run(&self.conn)?;
+        Ok(version < migrations::LATEST_VERSION)
+    }
+
+    pub fn schema_version(&self) -> Result<i64> {
+        Ok(migrations::schema_version(&self.conn)?)
+    }
+
+    // 只读连接，用于读连接池。结构迁移由写连接完成，这里只检查版本
+    pub fn open_reader<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
         )?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        migrations::check_version(&conn)?;
+        Ok(Database { conn, journal: RefCell::new(None) })
+    }
+
+    // 只读打开数据库并检查结构版本，不执行迁移
+    pub fn validate<P: AsRef<Path>>(db_path: P) -> Result<()> {
+        let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        // 读取 sqlite_master 以确认文件确实是 SQLite 数据库
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+        migrations::check_version(&conn)?;
         Ok(())
     }
 
     pub fn create_event(&self, request: CreateEventRequest) -> Result<TodoEvent> {
+        self.journaled("Create", || self.insert_event(request, ChangeCause::User))
+    }
+
+    fn insert_event(&self, request: CreateEventRequest, cause: ChangeCause) -> Result<TodoEvent> {
         let mut event = TodoEvent::new(
             request.name,
             request.description,
             request.tags,
             request.dependencies,
         );
+        event.due_at = request.due_at;
+        event.start_after = request.start_after;
+        event.estimate_minutes = request.estimate_minutes;
+
+        validate_estimate(event.estimate_minutes)?;
+        self.validate_dependencies(&event)?;
 
-        // 根据依赖关系自动计算状态
+        // 根据依赖关系和延后开始时间自动计算状态
         event.status = self.calculate_event_status(&event)?;
 
-        let tags_json = serde_json::to_string(&event.tags).unwrap();
-        let dependencies_json = serde_json::to_string(&event.dependencies).unwrap();
-        let status_str = match event.status {
-            EventStatus::Pending => "pending",
-            EventStatus::InProgress => "in_progress",
-            EventStatus::Completed => "completed",
-            EventStatus::Blocked => "blocked",
-        };
+        let tags_json = to_json(&event.tags)?;
 
         self.conn.execute(
-            "INSERT INTO events (id, name, description, tags, status, created_at, updated_at, dependencies)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT INTO events (id, name, description, tags, status, created_at, updated_at, due_at, start_after, completed_at, estimate_minutes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 event.id,
                 event.name,
                 event.description,
                 tags_json,
-                status_str,
+                status_to_str(event.status),
                 event.created_at.to_rfc3339(),
                 event.updated_at.to_rfc3339(),
-                dependencies_json
+                event.due_at.map(|t| t.to_rfc3339()),
+                event.start_after.map(|t| t.to_rfc3339()),
+                event.completed_at.map(|t| t.to_rfc3339()),
+                event.estimate_minutes
             ],
         )?;
+        self.save_dependencies(&event.id, &event.dependencies)?;
+        self.record_history(HistoryAction::Created, cause, None, Some(&event))?;
 
         Ok(event)
     }
 
     pub fn get_event(&self, id: &str) -> Result<Option<TodoEvent>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, description, tags, status, created_at, updated_at, dependencies
-             FROM events WHERE id = ?1"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events e WHERE e.id = ?1 AND e.deleted_at IS NULL",
+            EVENT_COLUMNS
+        ))?;
 
-        let event_iter = stmt.query_map([id], |row| {
-            let tags_json: String = row.get(3)?;
-            let dependencies_json: String = row.get(7)?;
-            let status_str: String = row.get(4)?;
-            let created_at_str: String = row.get(5)?;
-            let updated_at_str: String = row.get(6)?;
-
-            let tags: HashMap<String, String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            let dependencies: Vec<String> = serde_json::from_str(&dependencies_json).unwrap_or_default();
-            let status = match status_str.as_str() {
-                "pending" => EventStatus::Pending,
-                "in_progress" => EventStatus::InProgress,
-                "completed" => EventStatus::Completed,
-                "blocked" => EventStatus::Blocked,
-                _ => EventStatus::Pending,
-            };
+        let mut event_iter = stmt.query_map([id], event_from_row)?;
+        Ok(event_iter.next().transpose()?)
+    }
 
-            Ok(TodoEvent {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                tags,
-                status,
-                created_at: DateTime::parse_from_rfc3339(&created_at_str).unwrap().with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&updated_at_str).unwrap().with_timezone(&Utc),
-                dependencies,
-            })
-        })?;
+    // 查询事件，包括回收站中的事件
+    fn find_event(&self, id: &str) -> Result<Option<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events e WHERE e.id = ?1",
+            EVENT_COLUMNS
+        ))?;
 
-        for event in event_iter {
-            return Ok(Some(event?));
-        }
-        Ok(None)
+        let mut event_iter = stmt.query_map([id], event_from_row)?;
+        Ok(event_iter.next().transpose()?)
     }
 
     pub fn get_all_events(&self) -> Result<Vec<TodoEvent>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, description, tags, status, created_at, updated_at, dependencies
-             FROM events ORDER BY created_at DESC"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events e WHERE e.deleted_at IS NULL ORDER BY e.created_at DESC",
+            EVENT_COLUMNS
+        ))?;
 
-        let event_iter = stmt.query_map([], |row| {
-            let tags_json: String = row.get(3)?;
-            let dependencies_json: String = row.get(7)?;
-            let status_str: String = row.get(4)?;
-            let created_at_str: String = row.get(5)?;
-            let updated_at_str: String = row.get(6)?;
-
-            let tags: HashMap<String, String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            let dependencies: Vec<String> = serde_json::from_str(&dependencies_json).unwrap_or_default();
-            let status = match status_str.as_str() {
-                "pending" => EventStatus::Pending,
-                "in_progress" => EventStatus::InProgress,
-                "completed" => EventStatus::Completed,
-                "blocked" => EventStatus::Blocked,
-                _ => EventStatus::Pending,
-            };
+        let event_iter = stmt.query_map([], event_from_row)?;
+        Ok(event_iter.collect::<rusqlite::Result<_>>()?)
+    }
 
-            Ok(TodoEvent {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                tags,
-                status,
-                created_at: DateTime::parse_from_rfc3339(&created_at_str).unwrap().with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&updated_at_str).unwrap().with_timezone(&Utc),
-                dependencies,
-            })
-        })?;
+    // 查询某个事件直接依赖的事件
+    pub fn get_dependencies(&self, id: &str) -> Result<Vec<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM event_dependencies d
+             JOIN events e ON e.id = d.depends_on_id
+             WHERE d.event_id = ?1 AND e.deleted_at IS NULL
+             ORDER BY d.rowid",
+            EVENT_COLUMNS
+        ))?;
+
+        let event_iter = stmt.query_map([id], event_from_row)?;
+        Ok(event_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // 查询直接依赖于某个事件的事件
+    pub fn get_dependents(&self, id: &str) -> Result<Vec<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM event_dependencies d
+             JOIN events e ON e.id = d.event_id
+             WHERE d.depends_on_id = ?1 AND e.deleted_at IS NULL
+             ORDER BY e.created_at DESC",
+            EVENT_COLUMNS
+        ))?;
 
-        let mut events = Vec::new();
-        for event in event_iter {
-            events.push(event?);
+        let event_iter = stmt.query_map([id], event_from_row)?;
+        Ok(event_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn save_dependencies(&self, event_id: &str, dependencies: &[String]) -> Result<()> {
+        self.conn.execute("DELETE FROM event_dependencies WHERE event_id = ?1", [event_id])?;
+        let mut stmt = self.conn.prepare(
+            "INSERT OR IGNORE INTO event_dependencies (event_id, depends_on_id) VALUES (?1, ?2)"
+        )?;
+        for dep_id in dependencies {
+            stmt.execute([event_id, dep_id])?;
         }
-        Ok(events)
+        Ok(())
+    }
+
+    // 返回编辑后的事件和级联更新的事件，事件不存在时返回 None
+    pub fn update_event(&self, request: UpdateEventRequest) -> Result<Option<UpdateEventResult>> {
+        self.journaled("Edit", || self.apply_update(request, ChangeCause::User))
     }
 
-    pub fn update_event(&self, request: UpdateEventRequest) -> Result<Option<TodoEvent>> {
+    fn apply_update(&self, request: UpdateEventRequest, cause: ChangeCause) -> Result<Option<UpdateEventResult>> {
         if let Some(mut event) = self.get_event(&request.id)? {
+            let before = event.clone();
             let old_status = event.status;
-            println!("update event: {:?}", event);
-            
+
             // Check if dependencies are being updated
             let dependencies_changed = request.dependencies.is_some();
-            
+            let start_after_changed = request.start_after.is_some();
+            if let Some(estimate_minutes) = request.estimate_minutes {
+                validate_estimate(estimate_minutes)?;
+            }
+
             event.update(request);
 
-            // If dependencies changed, recalculate status
+            if dependencies_changed {
+                self.validate_dependencies(&event)?;
+            }
+
+            // If dependencies or the deferral changed, recalculate status
             // (unless the status is Completed or InProgress, which should be set manually)
-            if dependencies_changed && event.status != EventStatus::Completed && event.status != EventStatus::InProgress {
+            if (dependencies_changed || start_after_changed) && event.status != EventStatus::Completed && event.status != EventStatus::InProgress {
                 let calculated_status = self.calculate_event_status(&event)?;
                 println!("🔄 Dependencies changed, recalculated status: {:?} -> {:?}", event.status, calculated_status);
-                event.status = calculated_status;
+                event.set_status(calculated_status);
             }
 
-            let tags_json = serde_json::to_string(&event.tags).unwrap();
-            let dependencies_json = serde_json::to_string(&event.dependencies).unwrap();
-            let status_str = match event.status {
-                EventStatus::Pending => "pending",
-                EventStatus::InProgress => "in_progress",
-                EventStatus::Completed => "completed",
-                EventStatus::Blocked => "blocked",
-            };
+            let tags_json = to_json(&event.tags)?;
 
             self.conn.execute(
-                "UPDATE events SET name = ?1, description = ?2, tags = ?3, status = ?4, updated_at = ?5, dependencies = ?6
-                 WHERE id = ?7",
+                "UPDATE events SET name = ?1, description = ?2, tags = ?3, status = ?4, updated_at = ?5,
+                    due_at = ?6, start_after = ?7, completed_at = ?8, estimate_minutes = ?9, archived_at = ?10
+                 WHERE id = ?11",
                 params![
                     event.name,
                     event.description,
                     tags_json,
-                    status_str,
+                    status_to_str(event.status),
                     event.updated_at.to_rfc3339(),
-                    dependencies_json,
+                    event.due_at.map(|t| t.to_rfc3339()),
+                    event.start_after.map(|t| t.to_rfc3339()),
+                    event.completed_at.map(|t| t.to_rfc3339()),
+                    event.estimate_minutes,
+                    event.archived_at.map(|t| t.to_rfc3339()),
                     event.id
                 ],
             )?;
+            if dependencies_changed {
+                self.save_dependencies(&event.id, &event.dependencies)?;
+            }
+            self.record_history(HistoryAction::Updated, cause, Some(&before), Some(&event))?;
 
             // 如果状态发生变化，触发级联更新
-            if old_status != event.status {
-                self.update_event_status_cascade(&event.id, event.status)?;
-            }
+            let updated = if old_status != event.status {
+                self.propagate_status(&event.id)?
+            } else {
+                Vec::new()
+            };
 
-            Ok(Some(event))
+            Ok(Some(UpdateEventResult { event, updated }))
         } else {
             Ok(None)
         }
     }
 
-    pub fn delete_event(&self, id: &str) -> Result<bool> {
-        println!("🗄️ Database delete_event called with ID: {}", id);
-        let rows_affected = self.conn.execute("DELETE FROM events WHERE id = ?1", [id])?;
-        println!("🗄️ Rows affected: {}", rows_affected);
-        let success = rows_affected > 0;
-        println!("🗄️ Delete success: {}", success);
-        Ok(success)
+    pub fn delete_event(&self, id: &str, policy: DeletePolicy) -> Result<DeleteEventResult> {
+        self.journaled("Delete", || self.remove_event(id, policy, ChangeCause::User))
+    }
+
+    fn remove_event(&self, id: &str, policy: DeletePolicy, cause: ChangeCause) -> Result<DeleteEventResult> {
+        let mut result = DeleteEventResult { deleted: Vec::new(), updated: Vec::new() };
+        let Some(event) = self.get_event(id)? else {
+            return Ok(result);
+        };
+
+        let dependents = self.get_dependents(id)?;
+        match policy {
+            DeletePolicy::Refuse => {
+                if !dependents.is_empty() {
+                    let names: Vec<&str> = dependents.iter().map(|e| e.name.as_str()).collect();
+                    return Err(AppError::conflict(
+                        format!("Cannot delete \"{}\": still required by {}", event.name, names.join(", ")),
+                        dependents.iter().map(|e| e.id.clone()).collect(),
+                    ));
+                }
+                self.trash_event(&event, cause)?;
+                result.deleted.push(event.id);
+            }
+            DeletePolicy::Detach => {
+                // 移除指向该事件的依赖边，恢复该事件后不会重新阻塞依赖它的事件
+                self.conn.execute("DELETE FROM event_dependencies WHERE depends_on_id = ?1", [id])?;
+                self.trash_event(&event, cause)?;
+                result.deleted.push(event.id);
+                for dependent in &dependents {
+                    if let Some(dependent_event) = self.get_event(&dependent.id)? {
+                        self.record_history(HistoryAction::Updated, ChangeCause::Cascade, Some(dependent), Some(&dependent_event))?;
+                        if let Some(updated_event) = self.refresh_status(dependent_event, ChangeCause::Cascade)? {
+                            result.updated.push(updated_event);
+                        }
+                    }
+                }
+            }
+            DeletePolicy::Cascade => {
+                let subtree = self.get_transitive_dependents(id)?;
+                self.trash_event(&event, cause)?;
+                result.deleted.push(event.id.clone());
+                for doomed in &subtree {
+                    self.trash_event(doomed, ChangeCause::Cascade)?;
+                    result.deleted.push(doomed.id.clone());
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    // 移入回收站，依赖边保持不变
+    fn trash_event(&self, event: &TodoEvent, cause: ChangeCause) -> Result<()> {
+        let mut trashed = event.clone();
+        trashed.deleted_at = Some(Utc::now());
+        self.conn.execute(
+            "UPDATE events SET deleted_at = ?1 WHERE id = ?2",
+            params![trashed.deleted_at.map(|t| t.to_rfc3339()), trashed.id],
+        )?;
+        self.record_history(HistoryAction::Deleted, cause, Some(event), Some(&trashed))
+    }
+
+    // 回收站中的事件，最近删除的在前
+    pub fn list_trash(&self) -> Result<Vec<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events e WHERE e.deleted_at IS NOT NULL ORDER BY e.deleted_at DESC",
+            EVENT_COLUMNS
+        ))?;
+
+        let event_iter = stmt.query_map([], event_from_row)?;
+        Ok(event_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // 从回收站恢复事件，重新计算它和依赖它的事件的状态。
+    // 返回状态或依赖发生变化的事件（恢复的事件在第一个）；事件不在回收站中时返回空列表
+    pub fn restore_event(&self, id: &str) -> Result<Vec<TodoEvent>> {
+        self.journaled("Restore", || {
+            let Some(trashed) = self.find_event(id)?.filter(|event| event.deleted_at.is_some()) else {
+                return Ok(Vec::new());
+            };
+            self.conn.execute("UPDATE events SET deleted_at = NULL WHERE id = ?1", [id])?;
+            let Some(restored) = self.get_event(id)? else {
+                return Ok(Vec::new());
+            };
+            // 删除期间隐藏的依赖边可能与之后新增的依赖构成环
+            self.validate_dependencies(&restored)?;
+            self.record_history(HistoryAction::Restored, ChangeCause::User, Some(&trashed), Some(&restored))?;
+
+            let mut updated_events = Vec::new();
+            match self.refresh_status(restored.clone(), ChangeCause::Cascade)? {
+                Some(refreshed) => updated_events.push(refreshed),
+                None => updated_events.push(restored),
+            }
+            updated_events.extend(self.propagate_status(id)?);
+            Ok(updated_events)
+        })
+    }
+
+    // 永久删除回收站中的事件；before 为 None 时清空回收站，否则只删除在该时间之前移入的事件。
+    // 外键 ON DELETE CASCADE 会移除相关的依赖边
+    pub fn purge_trash(&self, before: Option<DateTime<Utc>>, cause: ChangeCause) -> Result<Vec<String>> {
+        self.transaction(|| {
+            let trashed: Vec<TodoEvent> = self
+                .list_trash()?
+                .into_iter()
+                .filter(|event| match (before, event.deleted_at) {
+                    (Some(before), Some(deleted_at)) => deleted_at < before,
+                    _ => true,
+                })
+                .collect();
+
+            let mut purged = Vec::new();
+            for event in trashed {
+                self.conn.execute("DELETE FROM events WHERE id = ?1", [&event.id])?;
+                self.record_history(HistoryAction::Purged, cause, Some(&event), None)?;
+                purged.push(event.id);
+            }
+            Ok(purged)
+        })
+    }
+
+    // 按保留期限自动清理回收站
+    pub fn purge_expired_trash(&self) -> Result<Vec<String>> {
+        let retention_days = self.trash_retention_days()?;
+        // 超出时间范围的保留期限（旧版本写入的设置）视为永久保留
+        let Some(before) = days_ago(retention_days) else {
+            return Ok(Vec::new());
+        };
+        self.purge_trash(Some(before), ChangeCause::Rule)
+    }
+
+    pub fn trash_retention_days(&self) -> Result<i64> {
+        Ok(self
+            .get_setting("trash_retention_days")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS))
+    }
+
+    pub fn set_trash_retention_days(&self, days: i64) -> Result<()> {
+        if days < 0 {
+            return Err(AppError::validation("days", "Retention period cannot be negative"));
+        }
+        if days > MAX_SETTING_DAYS {
+            return Err(AppError::validation("days", format!("Retention period cannot exceed {} days", MAX_SETTING_DAYS)));
+        }
+        self.set_setting("trash_retention_days", &days.to_string())
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?)
+    }
+
+    // 归档在 older_than 之前完成的事件（为 None 时归档所有已完成事件），返回被归档的事件 ID
+    pub fn archive_completed(&self, older_than: Option<DateTime<Utc>>) -> Result<Vec<String>> {
+        self.journaled_bulk("Archive", || self.archive_completed_before(older_than, ChangeCause::User))
+    }
+
+    // 自动归档策略：完成超过设定天数的事件，没有设置时不归档
+    pub fn archive_expired_completed(&self) -> Result<Vec<String>> {
+        // 超出时间范围的天数（旧版本写入的设置）不会有事件满足条件
+        let Some(before) = self.auto_archive_days()?.and_then(days_ago) else {
+            return Ok(Vec::new());
+        };
+        self.transaction(|| self.archive_completed_before(Some(before), ChangeCause::Rule))
+    }
+
+    fn archive_completed_before(&self, older_than: Option<DateTime<Utc>>, cause: ChangeCause) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM events e
+             WHERE e.status = 'completed' AND e.archived_at IS NULL AND e.deleted_at IS NULL
+             AND (?1 IS NULL OR e.completed_at < ?1)",
+            EVENT_COLUMNS
+        ))?;
+        let completed: Vec<TodoEvent> = stmt
+            .query_map([older_than.map(|t| t.to_rfc3339())], event_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let now = Utc::now();
+        let mut archived = Vec::new();
+        for event in completed {
+            let mut updated = event.clone();
+            updated.archived_at = Some(now);
+            self.conn.execute(
+                "UPDATE events SET archived_at = ?1 WHERE id = ?2",
+                params![now.to_rfc3339(), updated.id],
+            )?;
+            self.record_history(HistoryAction::Updated, cause, Some(&event), Some(&updated))?;
+            archived.push(updated.id);
+        }
+        Ok(archived)
+    }
+
+    pub fn auto_archive_days(&self) -> Result<Option<i64>> {
+        Ok(self
+            .get_setting("auto_archive_days")?
+            .and_then(|value| value.parse().ok()))
+    }
+
+    // None 关闭自动归档
+    pub fn set_auto_archive_days(&self, days: Option<i64>) -> Result<()> {
+        match days {
+            Some(days) if days < 0 => Err(AppError::validation("days", "Archive period cannot be negative")),
+            Some(days) if days > MAX_SETTING_DAYS => Err(AppError::validation(
+                "days",
+                format!("Archive period cannot exceed {} days", MAX_SETTING_DAYS),
+            )),
+            Some(days) => self.set_setting("auto_archive_days", &days.to_string()),
+            None => {
+                self.conn.execute("DELETE FROM settings WHERE key = 'auto_archive_days'", [])?;
+                Ok(())
+            }
+        }
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+
+    // 获取或续期写锁：锁空闲、已属于本实例或持有者的心跳已过期时写入本实例的心跳 now。
+    // 获取成功时返回 None，否则返回仍在使用数据库的实例
+    pub fn acquire_instance_lock(&self, instance_id: &str, host: &str, pid: u32, now: DateTime<Utc>) -> Result<Option<InstanceLock>> {
+        // 还没有锁表的旧数据库不会被其他实例锁定
+        if migrations::schema_version(&self.conn)? < migrations::INSTANCE_LOCK_VERSION {
+            return Ok(None);
+        }
+        let expired_before = now - chrono::Duration::seconds(INSTANCE_LOCK_TIMEOUT_SECS);
+        // 单条语句完成检查和写入，两个实例同时打开时只有一个能获取成功
+        let changes = self.conn.execute(
+            "INSERT INTO instance_lock (id, instance_id, host, pid, heartbeat_at) VALUES (1, ?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET
+                instance_id = excluded.instance_id, host = excluded.host,
+                pid = excluded.pid, heartbeat_at = excluded.heartbeat_at
+             WHERE instance_lock.instance_id = excluded.instance_id OR instance_lock.heartbeat_at < ?5",
+            params![instance_id, host, pid, now.to_rfc3339(), expired_before.to_rfc3339()],
+        )?;
+        if changes > 0 {
+            return Ok(None);
+        }
+        Ok(self.conn.query_row(
+            "SELECT instance_id, host, pid, heartbeat_at FROM instance_lock WHERE id = 1",
+            [],
+            |row| Ok(InstanceLock {
+                instance_id: row.get(0)?,
+                host: row.get(1)?,
+                pid: row.get(2)?,
+                heartbeat_at: parse_datetime(row, 3)?,
+            }),
+        ).optional()?)
+    }
+
+    // 正在写入 db_path 的其他实例（心跳未过期），文件不是数据库或没有锁表时返回 None
+    pub fn instance_lock_holder<P: AsRef<Path>>(db_path: P) -> Result<Option<InstanceLock>> {
+        let Ok(conn) = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+            return Ok(None);
+        };
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let expired_before = Utc::now() - chrono::Duration::seconds(INSTANCE_LOCK_TIMEOUT_SECS);
+        let holder = conn.query_row(
+            "SELECT instance_id, host, pid, heartbeat_at FROM instance_lock WHERE id = 1 AND heartbeat_at >= ?1",
+            [expired_before.to_rfc3339()],
+            |row| Ok(InstanceLock {
+                instance_id: row.get(0)?,
+                host: row.get(1)?,
+                pid: row.get(2)?,
+                heartbeat_at: parse_datetime(row, 3)?,
+            }),
+        );
+        match holder {
+            Ok(holder) => Ok(Some(holder)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            // 不是 SQLite 数据库，或者是没有锁表的旧版本数据库
+            Err(rusqlite::Error::SqliteFailure(error, _))
+                if matches!(error.code, ErrorCode::NotADatabase | ErrorCode::Unknown) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // 只有心跳仍是自己最后写入的 heartbeat_at 时才释放，
+    // 同一实例重新打开这个文件后写入的新锁不受影响
+    pub fn release_instance_lock(&self, instance_id: &str, heartbeat_at: DateTime<Utc>) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM instance_lock WHERE instance_id = ?1 AND heartbeat_at = ?2",
+            params![instance_id, heartbeat_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    // 在一个读事务中执行多个查询，使它们看到同一个数据库快照
+    pub fn snapshot<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        self.transaction(|| f(self))
+    }
+
+    // 在一个事务中执行写操作，出错时回滚。
+    // 已经处于事务中时直接执行，使内部调用合并到外层事务
+    fn transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let value = f()?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    // 在事务中执行一个可撤销的操作。record_history 会收集所有被修改的事件（包括级联修改）
+    // 在操作前的状态，操作完成后与操作后的状态一起写入操作日志
+    fn journaled<T>(&self, action: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.journal_operation(f, |before, after| {
+            // 第一个快照是操作直接作用的事件
+            let name = after
+                .first()
+                .and_then(|snapshot| snapshot.event.as_ref())
+                .or_else(|| before.first().and_then(|snapshot| snapshot.event.as_ref()))
+                .map(|event| event.name.clone())
+                .unwrap_or_default();
+            format!("{} \"{}\"", action, name)
+        })
+    }
+
+    // 批量操作，名称中使用涉及的事件数量
+    fn journaled_bulk<T>(&self, action: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.journal_operation(f, |before, _| format!("{} {} events", action, before.len()))
+    }
+
+    fn journal_operation<T>(
+        &self,
+        f: impl FnOnce() -> Result<T>,
+        label: impl FnOnce(&[EventSnapshot], &[EventSnapshot]) -> String,
+    ) -> Result<T> {
+        if self.journal.borrow().is_some() {
+            return f();
+        }
+        self.transaction(|| {
+            *self.journal.borrow_mut() = Some(Vec::new());
+            let value = f();
+            let before = self.journal.borrow_mut().take().unwrap_or_default();
+            let value = value?;
+            if !before.is_empty() {
+                let after = before
+                    .iter()
+                    .map(|snapshot| Ok(EventSnapshot { id: snapshot.id.clone(), event: self.find_event(&snapshot.id)? }))
+                    .collect::<Result<Vec<_>>>()?;
+                let label = label(&before, &after);
+                self.save_operation(label, before, after)?;
+            }
+            Ok(value)
+        })
+    }
+
+    fn save_operation(&self, label: String, before: Vec<EventSnapshot>, after: Vec<EventSnapshot>) -> Result<()> {
+        // 新操作使已撤销的操作无法再重做
+        self.conn.execute("DELETE FROM operations WHERE undone = 1", [])?;
+        self.conn.execute(
+            "INSERT INTO operations (label, before, after, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                label,
+                to_json(&before)?,
+                to_json(&after)?,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM operations WHERE id <= (SELECT MAX(id) FROM operations) - ?1",
+            [JOURNAL_LIMIT],
+        )?;
+        Ok(())
+    }
+
+    // 撤销最近一次操作，恢复所有涉及事件在操作前的状态；没有可撤销的操作时返回 None
+    pub fn undo(&self) -> Result<Option<UndoResult>> {
+        self.transaction(|| {
+            let operation = self.conn.query_row(
+                "SELECT id, label, before FROM operations WHERE undone = 0 ORDER BY id DESC LIMIT 1",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+            ).optional()?;
+            let Some((id, label, before_json)) = operation else {
+                return Ok(None);
+            };
+            // 日志损坏时报错并回滚，不能在没有恢复任何内容的情况下把操作标记为已撤销
+            let snapshots: Vec<EventSnapshot> = from_json(&before_json, 2)?;
+            let result = self.restore_snapshots(label, &snapshots)?;
+            self.conn.execute("UPDATE operations SET undone = 1 WHERE id = ?1", [id])?;
+            Ok(Some(result))
+        })
+    }
+
+    // 重做最早一次被撤销的操作
+    pub fn redo(&self) -> Result<Option<UndoResult>> {
+        self.transaction(|| {
+            let operation = self.conn.query_row(
+                "SELECT id, label, after FROM operations WHERE undone = 1 ORDER BY id LIMIT 1",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+            ).optional()?;
+            let Some((id, label, after_json)) = operation else {
+                return Ok(None);
+            };
+            let snapshots: Vec<EventSnapshot> = from_json(&after_json, 2)?;
+            let result = self.restore_snapshots(label, &snapshots)?;
+            self.conn.execute("UPDATE operations SET undone = 0 WHERE id = ?1", [id])?;
+            Ok(Some(result))
+        })
+    }
+
+    pub fn undo_state(&self) -> Result<UndoState> {
+        let undo = self.conn.query_row(
+            "SELECT label FROM operations WHERE undone = 0 ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        let redo = self.conn.query_row(
+            "SELECT label FROM operations WHERE undone = 1 ORDER BY id LIMIT 1",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(UndoState { undo, redo })
+    }
+
+    // 把事件恢复到快照中的状态：先写入或删除事件本身，再恢复依赖边，
+    // 这样快照之间相互引用的依赖可以按任意顺序恢复
+    fn restore_snapshots(&self, label: String, snapshots: &[EventSnapshot]) -> Result<UndoResult> {
+        let mut result = UndoResult { label, restored: Vec::new(), deleted: Vec::new() };
+
+        for snapshot in snapshots {
+            let current = self.find_event(&snapshot.id)?;
+            let action = match (&current, &snapshot.event) {
+                (None, Some(_)) => HistoryAction::Created,
+                (Some(_), None) => HistoryAction::Purged,
+                (Some(current), Some(target)) if current.deleted_at.is_some() && target.deleted_at.is_none() => {
+                    HistoryAction::Restored
+                }
+                (Some(current), Some(target)) if current.deleted_at.is_none() && target.deleted_at.is_some() => {
+                    HistoryAction::Deleted
+                }
+                _ => HistoryAction::Updated,
+            };
+            match &snapshot.event {
+                Some(event) => self.write_event(event)?,
+                None => {
+                    self.conn.execute("DELETE FROM events WHERE id = ?1", [&snapshot.id])?;
+                }
+            }
+            self.record_history(action, ChangeCause::User, current.as_ref(), snapshot.event.as_ref())?;
+        }
+
+        for snapshot in snapshots {
+            match &snapshot.event {
+                Some(event) => {
+                    // 之后的操作可能已经删除了某些前置事件
+                    let mut dependencies = Vec::new();
+                    for dep_id in &event.dependencies {
+                        if self.find_event(dep_id)?.is_some() {
+                            dependencies.push(dep_id.clone());
+                        }
+                    }
+                    self.save_dependencies(&event.id, &dependencies)?;
+                }
+                None => result.deleted.push(snapshot.id.clone()),
+            }
+        }
+
+        for snapshot in snapshots.iter().filter(|snapshot| snapshot.event.is_some()) {
+            if let Some(event) = self.find_event(&snapshot.id)? {
+                result.restored.push(event);
+            }
+        }
+        Ok(result)
+    }
+
+    // 按快照写入事件的所有列（不包括依赖），事件不存在时插入
+    fn write_event(&self, event: &TodoEvent) -> Result<()> {
+        let tags_json = to_json(&event.tags)?;
+        self.conn.execute(
+            "INSERT INTO events (id, name, description, tags, status, created_at, updated_at, due_at, start_after, completed_at, estimate_minutes, deleted_at, archived_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name, description = excluded.description, tags = excluded.tags,
+                status = excluded.status, created_at = excluded.created_at, updated_at = excluded.updated_at,
+                due_at = excluded.due_at, start_after = excluded.start_after,
+                completed_at = excluded.completed_at, estimate_minutes = excluded.estimate_minutes,
+                deleted_at = excluded.deleted_at, archived_at = excluded.archived_at",
+            params![
+                event.id,
+                event.name,
+                event.description,
+                tags_json,
+                status_to_str(event.status),
+                event.created_at.to_rfc3339(),
+                event.updated_at.to_rfc3339(),
+                event.due_at.map(|t| t.to_rfc3339()),
+                event.start_after.map(|t| t.to_rfc3339()),
+                event.completed_at.map(|t| t.to_rfc3339()),
+                event.estimate_minutes,
+                event.deleted_at.map(|t| t.to_rfc3339()),
+                event.archived_at.map(|t| t.to_rfc3339())
+            ],
+        )?;
+        Ok(())
+    }
+
+    // 校验依赖：拒绝自引用、不存在的事件和循环依赖
+    fn validate_dependencies(&self, event: &TodoEvent) -> Result<()> {
+        let all_events = self.get_all_events()?;
+        Ok(graph::validate_dependencies(event, &all_events)?)
     }
 
     // 计算事件的正确状态
     pub fn calculate_event_status(&self, event: &TodoEvent) -> Result<EventStatus> {
         println!("🧮 Calculating status for event: {} ({})", event.name, event.id);
-        
+
+        // 延后开始的事件在 start_after 之前不进入待办
+        if event.is_deferred() {
+            return Ok(EventStatus::Blocked);
+        }
+
         // 如果没有依赖，状态为待办
         if event.dependencies.is_empty() {
             println!("🧮 No dependencies, status: Pending");
@@ -227,7 +818,7 @@ impl Database {
         }
 
         println!("🧮 Checking {} dependencies", event.dependencies.len());
-        
+
         // 检查所有依赖事件的状态
         for dep_id in &event.dependencies {
             if let Some(dep_event) = self.get_event(dep_id)? {
@@ -249,139 +840,941 @@ impl Database {
         Ok(EventStatus::Pending)
     }
 
-    // 更新事件状态并级联更新依赖它的事件
+    // 更新事件状态并级联更新依赖它的事件，返回实际发生变化的事件
     pub fn update_event_status_cascade(&self, event_id: &str, new_status: EventStatus) -> Result<Vec<TodoEvent>> {
-        println!("🔄 Starting cascade update for event: {} -> {:?}", event_id, new_status);
+        self.journaled("Change status of", || self.cascade_status(event_id, new_status, ChangeCause::User))
+    }
+
+    fn cascade_status(&self, event_id: &str, new_status: EventStatus, cause: ChangeCause) -> Result<Vec<TodoEvent>> {
         let mut updated_events = Vec::new();
 
         // 更新当前事件状态
-        if let Some(mut event) = self.get_event(event_id)? {
-            let old_status = event.status;
-            println!("🔄 Current event status: {:?} -> {:?}", old_status, new_status);
-            event.status = new_status;
-            event.updated_at = Utc::now();
-
-            // 保存当前事件
-            let status_str = match event.status {
-                EventStatus::Pending => "pending",
-                EventStatus::InProgress => "in_progress",
-                EventStatus::Completed => "completed",
-                EventStatus::Blocked => "blocked",
-            };
+        let Some(mut event) = self.get_event(event_id)? else {
+            return Ok(updated_events);
+        };
+        // 重新打开为待办时，如果前置事件未完成则应为阻塞
+        let new_status = match new_status {
+            EventStatus::Pending => self.calculate_event_status(&event)?,
+            status => status,
+        };
+        if new_status == event.status {
+            return Ok(updated_events);
+        }
+        self.save_status(&mut event, new_status, cause)?;
+        updated_events.push(event);
 
-            self.conn.execute(
-                "UPDATE events SET status = ?1, updated_at = ?2 WHERE id = ?3",
-                [status_str, &event.updated_at.to_rfc3339(), &event.id],
-            )?;
+        updated_events.extend(self.propagate_status(event_id)?);
+        Ok(updated_events)
+    }
 
-            updated_events.push(event.clone());
-
-            // 如果事件从非完成状态变为完成状态，检查依赖它的事件
-                println!("🔄 Event completed, checking dependent events for: {}", event_id);
-                let all_events = self.get_all_events()?;
-                for dependent_event in all_events {
-                    // 检查这个事件是否依赖于刚完成的事件
-                    if dependent_event.dependencies.contains(&event_id.to_string()) && dependent_event.status == EventStatus::Blocked {
-                        println!("🔄 Found blocked dependent event: {} -> {}", dependent_event.name, dependent_event.id);
-                        // 重新计算依赖事件的状态
-                        let calculated_status = self.calculate_event_status(&dependent_event)?;
-                        println!("🔄 Calculated new status: {:?} (was: {:?})", calculated_status, dependent_event.status);
-                        if calculated_status != dependent_event.status {
-                            // 递归更新依赖事件的状态
-                            println!("🔄 Updating dependent event status");
-                            let cascade_updated = self.update_event_status_cascade(&dependent_event.id, calculated_status)?;
-                            updated_events.extend(cascade_updated);
-                        }
-                    }
+    // 按拓扑顺序重新计算所有（传递）依赖某个事件的事件，
+    // 保证每个事件计算时它的前置事件已经是最新状态。
+    // 完成 -> 解除阻塞，重新打开 -> 重新阻塞，两个方向都会沿依赖链传播
+    fn propagate_status(&self, event_id: &str) -> Result<Vec<TodoEvent>> {
+        let mut updated_events = Vec::new();
+        for dependent_event in self.get_transitive_dependents(event_id)? {
+            if let Some(updated_event) = self.refresh_status(dependent_event, ChangeCause::Cascade)? {
+                updated_events.push(updated_event);
             }
         }
-
         Ok(updated_events)
     }
 
-    pub fn filter_events(&self, filter: EventFilter) -> Result<Vec<TodoEvent>> {
-        let mut query = "SELECT id, name, description, tags, status, created_at, updated_at, dependencies FROM events WHERE 1=1".to_string();
-        let mut params: Vec<String> = Vec::new();
+    // 根据前置事件重新计算状态，状态有变化时保存并返回更新后的事件
+    fn refresh_status(&self, mut event: TodoEvent, cause: ChangeCause) -> Result<Option<TodoEvent>> {
+        // 已完成的事件保持不变
+        if event.status == EventStatus::Completed {
+            return Ok(None);
+        }
+        let calculated_status = self.calculate_event_status(&event)?;
+        let next_status = match (event.status, calculated_status) {
+            // 进行中的事件只会被重新阻塞，不会被退回待办
+            (EventStatus::InProgress, EventStatus::Pending) => EventStatus::InProgress,
+            (_, status) => status,
+        };
+        if next_status == event.status {
+            return Ok(None);
+        }
+        self.save_status(&mut event, next_status, cause)?;
+        Ok(Some(event))
+    }
 
-        if let Some(status) = filter.status {
-            let status_str = match status {
-                EventStatus::Pending => "pending",
-                EventStatus::InProgress => "in_progress",
-                EventStatus::Completed => "completed",
-                EventStatus::Blocked => "blocked",
-            };
-            query.push_str(" AND status = ?");
-            params.push(status_str.to_string());
+    fn save_status(&self, event: &mut TodoEvent, status: EventStatus, cause: ChangeCause) -> Result<()> {
+        let before = event.clone();
+        event.set_status(status);
+        self.conn.execute(
+            "UPDATE events SET status = ?1, updated_at = ?2, completed_at = ?3, archived_at = ?4 WHERE id = ?5",
+            params![
+                status_to_str(event.status),
+                event.updated_at.to_rfc3339(),
+                event.completed_at.map(|t| t.to_rfc3339()),
+                event.archived_at.map(|t| t.to_rfc3339()),
+                event.id
+            ],
+        )?;
+        self.record_history(HistoryAction::Updated, cause, Some(&before), Some(event))
+    }
+
+    // 追加一条变更历史；没有字段变化的更新不记录
+    fn record_history(
+        &self,
+        action: HistoryAction,
+        cause: ChangeCause,
+        before: Option<&TodoEvent>,
+        after: Option<&TodoEvent>,
+    ) -> Result<()> {
+        let Some(event_id) = after.or(before).map(|event| event.id.as_str()) else {
+            return Ok(());
+        };
+        let changes = history::diff_events(before, after);
+        if action == HistoryAction::Updated && changes.is_empty() {
+            return Ok(());
+        }
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            if !journal.iter().any(|snapshot| snapshot.id == event_id) {
+                journal.push(EventSnapshot { id: event_id.to_string(), event: before.cloned() });
+            }
         }
+        let changes_json = to_json(&changes)?;
+        self.conn.execute(
+            "INSERT INTO event_history (event_id, action, cause, changes, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                event_id,
+                history::action_to_str(action),
+                history::cause_to_str(cause),
+                changes_json,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
 
-        if let Some(search) = filter.search {
-            if !search.is_empty() {
-                query.push_str(" AND (name LIKE ? OR description LIKE ?)");
-                let search_pattern = format!("%{}%", search);
-                params.push(search_pattern.clone());
-                params.push(search_pattern);
+    // 查询事件的变更历史（按时间顺序），已删除事件的历史仍然可以查询
+    pub fn get_event_history(&self, event_id: &str) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, event_id, action, cause, changes, recorded_at FROM event_history
+             WHERE event_id = ?1 ORDER BY id",
+        )?;
+        let entry_iter = stmt.query_map([event_id], |row| {
+            let action: String = row.get(2)?;
+            let cause: String = row.get(3)?;
+            let changes_json: String = row.get(4)?;
+            Ok(HistoryEntry {
+                id: row.get(0)?,
+                event_id: row.get(1)?,
+                action: history::action_from_str(&action),
+                cause: history::cause_from_str(&cause),
+                changes: from_json(&changes_json, 4)?,
+                recorded_at: parse_datetime(row, 5)?,
+            })
+        })?;
+        Ok(entry_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // 延后时间已到的阻塞事件重新计算状态，返回被释放的事件
+    pub fn release_deferred_events(&self) -> Result<Vec<TodoEvent>> {
+        self.transaction(|| {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT {} FROM events e WHERE e.status = 'blocked' AND e.start_after <= ?1 AND e.deleted_at IS NULL",
+                EVENT_COLUMNS
+            ))?;
+            let due: Vec<TodoEvent> = stmt
+                .query_map([Utc::now().to_rfc3339()], event_from_row)?
+                .collect::<rusqlite::Result<_>>()?;
+
+            let mut released = Vec::new();
+            for event in due {
+                if let Some(updated_event) = self.refresh_status(event, ChangeCause::Rule)? {
+                    released.push(updated_event);
+                }
+            }
+            Ok(released)
+        })
+    }
+
+    // 查询所有直接或间接依赖于某个事件的事件，按拓扑顺序返回（前置事件在前）
+    pub fn get_transitive_dependents(&self, id: &str) -> Result<Vec<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH RECURSIVE downstream(id) AS (
+                SELECT d.event_id FROM event_dependencies d JOIN events x ON x.id = d.event_id
+                WHERE d.depends_on_id = ?1 AND x.deleted_at IS NULL
+                UNION
+                SELECT d.event_id FROM event_dependencies d
+                JOIN downstream ON d.depends_on_id = downstream.id
+                JOIN events x ON x.id = d.event_id
+                WHERE x.deleted_at IS NULL
+            )
+            SELECT {} FROM events e WHERE e.id IN downstream",
+            EVENT_COLUMNS
+        ))?;
+
+        let event_iter = stmt.query_map([id], event_from_row)?;
+        let events: Vec<TodoEvent> = event_iter.collect::<rusqlite::Result<_>>()?;
+        Ok(graph::topological_sort(events))
+    }
+
+    // 查询某个事件直接或间接依赖的所有事件
+    pub fn get_transitive_dependencies(&self, id: &str) -> Result<Vec<TodoEvent>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH RECURSIVE upstream(id) AS (
+                SELECT d.depends_on_id FROM event_dependencies d JOIN events x ON x.id = d.depends_on_id
+                WHERE d.event_id = ?1 AND x.deleted_at IS NULL
+                UNION
+                SELECT d.depends_on_id FROM event_dependencies d
+                JOIN upstream ON d.event_id = upstream.id
+                JOIN events x ON x.id = d.depends_on_id
+                WHERE x.deleted_at IS NULL
+            )
+            SELECT {} FROM events e WHERE e.id IN upstream",
+            EVENT_COLUMNS
+        ))?;
+
+        let event_iter = stmt.query_map([id], event_from_row)?;
+        Ok(event_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // 关键路径分析：指定目标时分析目标及其所有前置事件，否则分析所有未完成事件。
+    // 已完成的事件不参与计算；目标不存在时返回 None
+    pub fn critical_path(&self, target_id: Option<&str>) -> Result<Option<CriticalPathReport>> {
+        let scope = match target_id {
+            Some(target_id) => {
+                let Some(target) = self.get_event(target_id)? else {
+                    return Ok(None);
+                };
+                let mut scope = self.get_transitive_dependencies(target_id)?;
+                scope.push(target);
+                scope
             }
+            None => self.get_all_events()?,
+        };
+        let open_events: Vec<TodoEvent> = scope
+            .into_iter()
+            .filter(|event| event.status != EventStatus::Completed)
+            .collect();
+        Ok(Some(graph::critical_path(open_events)))
+    }
+
+    // 按综合分数给可以立即开始的事件排序
+    pub fn next_tasks(&self, limit: Option<usize>, weights: &RankingWeights) -> Result<Vec<RankedTask>> {
+        let all_events = self.get_all_events()?;
+        let mut ranked = ranking::rank_ready_events(&all_events, weights, Utc::now());
+        if let Some(limit) = limit {
+            ranked.truncate(limit);
+        }
+        Ok(ranked)
+    }
+
+    // 全文搜索，按 BM25 相关度排序（名称权重最高）。
+    // 支持 "短语" 和 前缀* 查询，其余输入按普通词匹配
+    pub fn search_events(&self, query: &str, limit: usize, include_archived: bool) -> Result<Vec<SearchResult>> {
+        let Some(fts_query) = filters::fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {},
+                highlight(events_fts, 0, '<mark>', '</mark>'),
+                snippet(events_fts, -1, '<mark>', '</mark>', '…', 16),
+                bm25(events_fts, 10.0, 4.0, 2.0) AS rank
+             FROM events_fts
+             JOIN event_search s ON s.key = events_fts.rowid
+             JOIN events e ON e.id = s.event_id
+             WHERE events_fts MATCH ?1 AND e.deleted_at IS NULL AND (?3 OR e.archived_at IS NULL)
+             ORDER BY rank, e.created_at DESC
+             LIMIT ?2",
+            EVENT_COLUMNS
+        ))?;
+
+        let result_iter = stmt.query_map(params![fts_query, limit as i64, include_archived], |row| {
+            let rank: f64 = row.get(EVENT_COLUMN_COUNT + 2)?;
+            Ok(SearchResult {
+                event: event_from_row(row)?,
+                name_highlight: row.get(EVENT_COLUMN_COUNT)?,
+                snippet: row.get(EVENT_COLUMN_COUNT + 1)?,
+                score: -rank,
+            })
+        })?;
+        Ok(result_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // 按过滤和排序条件分页查询，使用键集分页，翻页期间插入的新事件不会导致重复或遗漏
+    pub fn filter_events(&self, filter: EventFilter, page: PageRequest) -> Result<Page<TodoEvent>> {
+        let condition = filters::filter_condition(&filter, Utc::now())?;
+        // 回收站中的事件不出现在查询结果中，已归档的事件需要显式包含
+        let mut base = vec![filters::SqlCondition::new("e.deleted_at IS NULL", Vec::new())];
+        if !filter.include_archived.unwrap_or(false) {
+            base.push(filters::SqlCondition::new("e.archived_at IS NULL", Vec::new()));
         }
+        base.push(condition);
+        let condition = filters::SqlCondition::and(base);
+        let order = filters::sort_order(filter.sort.as_ref());
 
-        query.push_str(" ORDER BY created_at DESC");
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM events e WHERE {}", condition.sql),
+            params_from_iter(condition.params.iter()),
+            |row| row.get(0),
+        )?;
+
+        let mut conditions = vec![condition];
+        if let Some(cursor) = &page.cursor {
+            let values = order
+                .decode_cursor(&filter, cursor)
+                .ok_or_else(|| AppError::validation("cursor", "Invalid or expired page cursor"))?;
+            conditions.push(order.after(&values));
+        }
+        let condition = filters::SqlCondition::and(conditions);
+
+        let mut query = format!(
+            "SELECT {}{} FROM events e{} WHERE {} ORDER BY {}",
+            EVENT_COLUMNS, order.select_terms(), order.joins, condition.sql, order.order_by()
+        );
+        let mut params: Vec<Value> = order.params.iter().cloned().chain(condition.params).collect();
+        if let Some(limit) = page.limit {
+            // 多取一行用于判断是否还有下一页
+            query.push_str(" LIMIT ?");
+            params.push(Value::Integer(limit as i64 + 1));
+        }
 
         let mut stmt = self.conn.prepare(&query)?;
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p as &dyn rusqlite::ToSql).collect();
-
-        let event_iter = stmt.query_map(&param_refs[..], |row| {
-            let tags_json: String = row.get(3)?;
-            let dependencies_json: String = row.get(7)?;
-            let status_str: String = row.get(4)?;
-            let created_at_str: String = row.get(5)?;
-            let updated_at_str: String = row.get(6)?;
-
-            let tags: HashMap<String, String> = serde_json::from_str(&tags_json).unwrap_or_default();
-            let dependencies: Vec<String> = serde_json::from_str(&dependencies_json).unwrap_or_default();
-            let status = match status_str.as_str() {
-                "pending" => EventStatus::Pending,
-                "in_progress" => EventStatus::InProgress,
-                "completed" => EventStatus::Completed,
-                "blocked" => EventStatus::Blocked,
-                _ => EventStatus::Pending,
-            };
+        let term_count = order.terms.len();
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            let sort_values = (0..term_count)
+                .map(|i| row.get::<_, Value>(EVENT_COLUMN_COUNT + i))
+                .collect::<rusqlite::Result<Vec<Value>>>()?;
+            Ok((event_from_row(row)?, sort_values))
+        })?;
+        let mut rows: Vec<(TodoEvent, Vec<Value>)> = rows.collect::<rusqlite::Result<_>>()?;
 
-            Ok(TodoEvent {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
+        let mut next_cursor = None;
+        if let Some(limit) = page.limit {
+            if rows.len() > limit {
+                rows.truncate(limit);
+                next_cursor = rows.last().map(|(_, sort_values)| order.encode_cursor(&filter, sort_values));
+            }
+        }
+
+        Ok(Page {
+            items: rows.into_iter().map(|(event, _)| event).collect(),
+            next_cursor,
+            total: total as usize,
+        })
+    }
+
+    pub fn create_view(&self, request: CreateViewRequest) -> Result<SavedView> {
+        let now = Utc::now();
+        let view = SavedView {
+            id: Uuid::new_v4().to_string(),
+            name: request.name.trim().to_string(),
+            filter: request.filter,
+            created_at: now,
+            updated_at: now,
+        };
+        self.validate_view(&view)?;
+
+        let filter_json = to_json(&view.filter)?;
+        self.conn.execute(
+            "INSERT INTO views (id, name, filter, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                view.id,
+                view.name,
+                filter_json,
+                view.created_at.to_rfc3339(),
+                view.updated_at.to_rfc3339()
+            ],
+        )?;
+        Ok(view)
+    }
+
+    pub fn get_view(&self, id: &str) -> Result<Option<SavedView>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, filter, created_at, updated_at FROM views WHERE id = ?1",
+        )?;
+        let mut view_iter = stmt.query_map([id], view_from_row)?;
+        Ok(view_iter.next().transpose()?)
+    }
+
+    pub fn get_views(&self) -> Result<Vec<SavedView>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, filter, created_at, updated_at FROM views ORDER BY name COLLATE NOCASE",
+        )?;
+        let view_iter = stmt.query_map([], view_from_row)?;
+        Ok(view_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn update_view(&self, request: UpdateViewRequest) -> Result<Option<SavedView>> {
+        let Some(mut view) = self.get_view(&request.id)? else {
+            return Ok(None);
+        };
+        if let Some(name) = request.name {
+            view.name = name.trim().to_string();
+        }
+        if let Some(filter) = request.filter {
+            view.filter = filter;
+        }
+        view.updated_at = Utc::now();
+        self.validate_view(&view)?;
+
+        let filter_json = to_json(&view.filter)?;
+        self.conn.execute(
+            "UPDATE views SET name = ?2, filter = ?3, updated_at = ?4 WHERE id = ?1",
+            params![view.id, view.name, filter_json, view.updated_at.to_rfc3339()],
+        )?;
+        Ok(Some(view))
+    }
+
+    pub fn delete_view(&self, id: &str) -> Result<bool> {
+        let changes = self.conn.execute("DELETE FROM views WHERE id = ?1", [id])?;
+        Ok(changes > 0)
+    }
+
+    // 执行视图；视图不存在时返回 None
+    pub fn execute_view(&self, id: &str, page: PageRequest) -> Result<Option<Page<TodoEvent>>> {
+        let Some(view) = self.get_view(id)? else {
+            return Ok(None);
+        };
+        self.filter_events(view.filter, page).map(Some)
+    }
+
+    // 视图名称不能为空且不能重复（不区分大小写），查询文本必须能够解析
+    fn validate_view(&self, view: &SavedView) -> Result<()> {
+        if view.name.is_empty() {
+            return Err(AppError::validation("name", "View name cannot be empty"));
+        }
+        let duplicate: Option<String> = self.conn.query_row(
+            "SELECT id FROM views WHERE name = ?1 COLLATE NOCASE AND id != ?2",
+            params![view.name, view.id],
+            |row| row.get(0),
+        ).optional()?;
+        if let Some(duplicate) = duplicate {
+            return Err(AppError::conflict(format!("A view named \"{}\" already exists", view.name), vec![duplicate]));
+        }
+        filters::filter_condition(&view.filter, Utc::now())?;
+        Ok(())
+    }
+}
+
+pub(crate) fn status_to_str(status: EventStatus) -> &'static str {
+    match status {
+        EventStatus::Pending => "pending",
+        EventStatus::InProgress => "in_progress",
+        EventStatus::Completed => "completed",
+        EventStatus::Blocked => "blocked",
+    }
+}
+
+fn status_from_str(status: &str) -> EventStatus {
+    match status {
+        "pending" => EventStatus::Pending,
+        "in_progress" => EventStatus::InProgress,
+        "completed" => EventStatus::Completed,
+        "blocked" => EventStatus::Blocked,
+        _ => EventStatus::Pending,
+    }
+}
+
+// 把 EVENT_COLUMNS 查询出的一行转换为事件
+fn event_from_row(row: &Row) -> rusqlite::Result<TodoEvent> {
+    let tags_json: String = row.get(3)?;
+    let dependencies_json: String = row.get(7)?;
+    let status_str: String = row.get(4)?;
+
+    // 标签或依赖损坏时报错，而不是把事件显示为没有标签和依赖
+    let tags: HashMap<String, String> = from_json(&tags_json, 3)?;
+    let dependencies: Vec<String> = from_json(&dependencies_json, 7)?;
+
+    Ok(TodoEvent {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        tags,
+        status: status_from_str(&status_str),
+        created_at: parse_datetime(row, 5)?,
+        updated_at: parse_datetime(row, 6)?,
+        dependencies,
+        due_at: parse_optional_datetime(row.get(8)?),
+        start_after: parse_optional_datetime(row.get(9)?),
+        completed_at: parse_optional_datetime(row.get(10)?),
+        estimate_minutes: row.get(11)?,
+        deleted_at: parse_optional_datetime(row.get(12)?),
+        archived_at: parse_optional_datetime(row.get(13)?),
+    })
+}
+
+fn view_from_row(row: &Row) -> rusqlite::Result<SavedView> {
+    let filter_json: String = row.get(2)?;
+
+    Ok(SavedView {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        // 过滤条件损坏时报错，而不是变成匹配所有事件的视图
+        filter: from_json(&filter_json, 2)?,
+        created_at: parse_datetime(row, 3)?,
+        updated_at: parse_datetime(row, 4)?,
+    })
+}
+
+// Windows 的 UNC 路径（\\server\share\...）位于网络共享上。
+// 映射的网络驱动器和挂载的共享目录无法从路径判断
+fn is_network_path(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    match path.strip_prefix(r"\\?\") {
+        Some(rest) => rest.starts_with(r"UNC\"),
+        None => path.starts_with(r"\\") && !path.starts_with(r"\\.\"),
+    }
+}
+
+// 预估工时不能为负数，也不能超过关键路径计算支持的上限
+fn validate_estimate(estimate_minutes: Option<i64>) -> Result<()> {
+    match estimate_minutes {
+        Some(minutes) if minutes < 0 => Err(AppError::validation("estimate_minutes", "Estimate cannot be negative")),
+        Some(minutes) if minutes > graph::MAX_ESTIMATE_MINUTES => Err(AppError::validation(
+            "estimate_minutes",
+            format!("Estimate cannot exceed {} minutes", graph::MAX_ESTIMATE_MINUTES),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// 当前时间之前 days 天，超出可表示的时间范围时返回 None
+fn days_ago(days: i64) -> Option<DateTime<Utc>> {
+    Utc::now().checked_sub_signed(chrono::Duration::try_days(days)?)
+}
+
+fn parse_optional_datetime(value: Option<String>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+// 读取 RFC 3339 时间列，格式不正确时返回转换错误
+fn parse_datetime(row: &Row, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let value: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+// 解析第 index 列中的 JSON，格式不正确时返回转换错误
+fn from_json<T: DeserializeOwned>(value: &str, index: usize) -> rusqlite::Result<T> {
+    serde_json::from_str(value)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SortDirection, SortField, SortKey, SortSpec};
+
+    fn create(db: &Database, name: &str, dependencies: Vec<String>) -> TodoEvent {
+        db.create_event(CreateEventRequest {
+            name: name.to_string(),
+            description: String::new(),
+            tags: HashMap::new(),
+            dependencies,
+            due_at: None,
+            start_after: None,
+            estimate_minutes: None,
+        })
+        .unwrap()
+    }
+
+    fn update_request(id: &str) -> UpdateEventRequest {
+        UpdateEventRequest {
+            id: id.to_string(),
+            name: None,
+            description: None,
+            tags: None,
+            status: None,
+            dependencies: None,
+            due_at: None,
+            start_after: None,
+            estimate_minutes: None,
+        }
+    }
+
+    fn status(db: &Database, id: &str) -> EventStatus {
+        db.get_event(id).unwrap().unwrap().status
+    }
+
+    // A <- B <- C：B 依赖 A，C 依赖 B
+    fn chain(db: &Database) -> (String, String, String) {
+        let a = create(db, "A", vec![]);
+        let b = create(db, "B", vec![a.id.clone()]);
+        let c = create(db, "C", vec![b.id.clone()]);
+        (a.id, b.id, c.id)
+    }
+
+    #[test]
+    fn completing_a_chain_unblocks_each_level() {
+        let db = Database::new(":memory:").unwrap();
+        let (a, b, c) = chain(&db);
+        assert_eq!(status(&db, &b), EventStatus::Blocked);
+        assert_eq!(status(&db, &c), EventStatus::Blocked);
+
+        let updated = db.update_event_status_cascade(&a, EventStatus::Completed).unwrap();
+        assert_eq!(updated.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec![a.as_str(), b.as_str()]);
+        assert_eq!(status(&db, &b), EventStatus::Pending);
+        assert_eq!(status(&db, &c), EventStatus::Blocked);
+
+        db.update_event_status_cascade(&b, EventStatus::Completed).unwrap();
+        assert_eq!(status(&db, &c), EventStatus::Pending);
+    }
+
+    #[test]
+    fn reopening_the_root_reblocks_the_chain() {
+        let db = Database::new(":memory:").unwrap();
+        let (a, b, c) = chain(&db);
+        db.update_event_status_cascade(&a, EventStatus::Completed).unwrap();
+        db.update_event_status_cascade(&b, EventStatus::Completed).unwrap();
+
+        // 已完成的 B 保持完成，C 仍然可以开始
+        db.update_event_status_cascade(&a, EventStatus::Pending).unwrap();
+        assert_eq!(status(&db, &a), EventStatus::Pending);
+        assert_eq!(status(&db, &b), EventStatus::Completed);
+        assert_eq!(status(&db, &c), EventStatus::Pending);
+
+        // 重新打开 B 后，B 和 C 都被阻塞
+        db.update_event_status_cascade(&b, EventStatus::Pending).unwrap();
+        assert_eq!(status(&db, &b), EventStatus::Blocked);
+        assert_eq!(status(&db, &c), EventStatus::Blocked);
+    }
+
+    #[test]
+    fn reopening_through_update_event_reblocks_dependents() {
+        let db = Database::new(":memory:").unwrap();
+        let (a, b, c) = chain(&db);
+        db.update_event_status_cascade(&a, EventStatus::Completed).unwrap();
+        assert_eq!(status(&db, &b), EventStatus::Pending);
+
+        let mut request = update_request(&a);
+        request.status = Some(EventStatus::Pending);
+        let result = db.update_event(request).unwrap().unwrap();
+        assert_eq!(result.event.status, EventStatus::Pending);
+        // 只返回状态发生变化的 B，C 原本就是阻塞的
+        assert_eq!(result.updated.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec![b.as_str()]);
+        assert_eq!(status(&db, &a), EventStatus::Pending);
+        assert_eq!(status(&db, &b), EventStatus::Blocked);
+        assert_eq!(status(&db, &c), EventStatus::Blocked);
+    }
+
+    #[test]
+    fn in_progress_dependent_is_reblocked_but_not_demoted() {
+        let db = Database::new(":memory:").unwrap();
+        let (a, b, _) = chain(&db);
+        db.update_event_status_cascade(&a, EventStatus::Completed).unwrap();
+        db.update_event_status_cascade(&b, EventStatus::InProgress).unwrap();
+
+        // 完成另一个前置事件会重新计算 B，进行中的事件不会被改回待办
+        let d = create(&db, "D", vec![]);
+        let mut request = update_request(&b);
+        request.dependencies = Some(vec![a.clone(), d.id.clone()]);
+        db.update_event(request).unwrap();
+        let updated = db.update_event_status_cascade(&d.id, EventStatus::Completed).unwrap();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(status(&db, &b), EventStatus::InProgress);
+
+        // 重新打开前置事件时进行中的事件被阻塞
+        db.update_event_status_cascade(&a, EventStatus::Pending).unwrap();
+        assert_eq!(status(&db, &b), EventStatus::Blocked);
+
+        db.update_event_status_cascade(&a, EventStatus::Completed).unwrap();
+        assert_eq!(status(&db, &b), EventStatus::Pending);
+    }
+
+    #[test]
+    fn invalid_dependencies_are_not_written() {
+        let db = Database::new(":memory:").unwrap();
+        let (a, b, c) = chain(&db);
+        let history = db.get_event_history(&a).unwrap().len();
+        let undo_state = db.undo_state().unwrap();
+
+        let mut request = update_request(&a);
+        request.name = Some("Renamed".to_string());
+        request.dependencies = Some(vec![c.clone()]);
+        let error = db.update_event(request).unwrap_err();
+        assert_eq!(error.code(), "Conflict");
+
+        let event = db.get_event(&a).unwrap().unwrap();
+        assert_eq!(event.name, "A");
+        assert!(event.dependencies.is_empty());
+        assert_eq!(db.get_dependents(&c).unwrap().len(), 0);
+        assert_eq!(db.get_event_history(&a).unwrap().len(), history);
+        assert_eq!(db.undo_state().unwrap().undo, undo_state.undo);
+
+        // 创建时引用不存在的事件或形成自引用都不会写入事件
+        let error = db
+            .create_event(CreateEventRequest {
+                name: "D".to_string(),
+                description: String::new(),
+                tags: HashMap::new(),
+                dependencies: vec![b.clone(), "missing".to_string()],
+                due_at: None,
+                start_after: None,
+                estimate_minutes: None,
+            })
+            .unwrap_err();
+        assert_eq!(error.code(), "Validation");
+        assert_eq!(db.get_all_events().unwrap().len(), 3);
+        assert_eq!(db.get_dependents(&b).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn estimates_must_be_in_range() {
+        let db = Database::new(":memory:").unwrap();
+        let event = create(&db, "A", vec![]);
+
+        for estimate in [-1, graph::MAX_ESTIMATE_MINUTES + 1, i64::MAX] {
+            let mut request = update_request(&event.id);
+            request.estimate_minutes = Some(Some(estimate));
+            assert_eq!(db.update_event(request).unwrap_err().code(), "Validation");
+        }
+        assert_eq!(db.get_event(&event.id).unwrap().unwrap().estimate_minutes, None);
+
+        let mut request = update_request(&event.id);
+        request.estimate_minutes = Some(Some(graph::MAX_ESTIMATE_MINUTES));
+        db.update_event(request).unwrap();
+
+        let error = db
+            .create_event(CreateEventRequest {
+                name: "B".to_string(),
+                description: String::new(),
+                tags: HashMap::new(),
+                dependencies: vec![],
+                due_at: None,
+                start_after: None,
+                estimate_minutes: Some(-30),
+            })
+            .unwrap_err();
+        assert_eq!(error.code(), "Validation");
+        assert_eq!(db.get_all_events().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn trash_retention_is_bounded() {
+        let db = Database::new(":memory:").unwrap();
+        assert!(db.set_trash_retention_days(100_000_000).is_err());
+        db.set_trash_retention_days(MAX_SETTING_DAYS).unwrap();
+
+        // 旧版本可能已经保存了超出范围的值，清理时不能崩溃，也不能清空回收站
+        let event = create(&db, "A", vec![]);
+        db.delete_event(&event.id, DeletePolicy::Refuse).unwrap();
+        db.set_setting("trash_retention_days", "100000000").unwrap();
+        assert!(db.purge_expired_trash().unwrap().is_empty());
+        assert_eq!(db.list_trash().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn auto_archive_days_is_bounded() {
+        let db = Database::new(":memory:").unwrap();
+        assert!(db.set_auto_archive_days(Some(100_000_000)).is_err());
+        db.set_auto_archive_days(Some(MAX_SETTING_DAYS)).unwrap();
+
+        let event = create(&db, "A", vec![]);
+        db.update_event_status_cascade(&event.id, EventStatus::Completed).unwrap();
+        db.set_setting("auto_archive_days", "100000000").unwrap();
+        assert!(db.archive_expired_completed().unwrap().is_empty());
+        assert!(db.get_event(&event.id).unwrap().unwrap().archived_at.is_none());
+    }
+
+    #[test]
+    fn restoring_a_detached_event_does_not_reattach_dependents() {
+        let db = Database::new(":memory:").unwrap();
+        let a = create(&db, "A", vec![]);
+        let b = create(&db, "B", vec![a.id.clone()]);
+
+        db.delete_event(&a.id, DeletePolicy::Detach).unwrap();
+        assert!(db.get_event(&b.id).unwrap().unwrap().dependencies.is_empty());
+        assert_eq!(status(&db, &b.id), EventStatus::Pending);
+
+        db.restore_event(&a.id).unwrap();
+        assert!(db.get_event(&b.id).unwrap().unwrap().dependencies.is_empty());
+        assert_eq!(status(&db, &b.id), EventStatus::Pending);
+
+        // 撤销恢复和删除后依赖关系回到删除前
+        db.undo().unwrap();
+        db.undo().unwrap();
+        assert_eq!(db.get_event(&b.id).unwrap().unwrap().dependencies, vec![a.id.clone()]);
+        assert_eq!(status(&db, &b.id), EventStatus::Blocked);
+
+        // 重做删除后依赖边再次被移除
+        db.redo().unwrap();
+        db.restore_event(&a.id).unwrap();
+        assert!(db.get_event(&b.id).unwrap().unwrap().dependencies.is_empty());
+    }
+
+    #[test]
+    fn cascade_delete_keeps_edges_for_restore() {
+        let db = Database::new(":memory:").unwrap();
+        let a = create(&db, "A", vec![]);
+        let b = create(&db, "B", vec![a.id.clone()]);
+
+        db.delete_event(&a.id, DeletePolicy::Cascade).unwrap();
+        db.restore_event(&a.id).unwrap();
+        db.restore_event(&b.id).unwrap();
+        assert_eq!(db.get_event(&b.id).unwrap().unwrap().dependencies, vec![a.id.clone()]);
+        assert_eq!(status(&db, &b.id), EventStatus::Blocked);
+    }
+
+    #[test]
+    fn search_finds_the_right_event_after_vacuum() {
+        let db = Database::new(":memory:").unwrap();
+        let first = create(&db, "alpha", vec![]);
+        create(&db, "bravo", vec![]);
+        let third = create(&db, "charlie", vec![]);
+        db.delete_event(&first.id, DeletePolicy::Refuse).unwrap();
+        db.purge_trash(None, ChangeCause::User).unwrap();
+        // VACUUM 可能重新编号没有 INTEGER PRIMARY KEY 的表的 rowid，这里直接模拟重新编号
+        db.conn.execute_batch("VACUUM; UPDATE events SET rowid = rowid + 100;").unwrap();
+
+        let results = db.search_events("charlie", 10, false).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].event.id, third.id);
+    }
+
+    #[test]
+    fn search_follows_updates_and_deletes() {
+        let db = Database::new(":memory:").unwrap();
+        let first = create(&db, "alpha", vec![]);
+        let second = create(&db, "bravo", vec![]);
+        let search = |text: &str| -> Vec<String> {
+            db.search_events(text, 10, false).unwrap().into_iter().map(|result| result.event.id).collect()
+        };
+
+        let mut request = update_request(&first.id);
+        request.name = Some("charlie".to_string());
+        request.tags = Some(HashMap::from([("color".to_string(), "crimson".to_string())]));
+        db.update_event(request).unwrap();
+        assert!(search("alpha").is_empty());
+        assert_eq!(search("charlie"), vec![first.id.clone()]);
+        assert_eq!(search("crimson"), vec![first.id.clone()]);
+        let results = db.search_events("charlie", 10, false).unwrap();
+        assert_eq!(results[0].name_highlight, "<mark>charlie</mark>");
+
+        // 只修改状态不会改动索引
+        db.update_event_status_cascade(&second.id, EventStatus::InProgress).unwrap();
+        assert_eq!(search("bravo"), vec![second.id.clone()]);
+
+        db.delete_event(&first.id, DeletePolicy::Refuse).unwrap();
+        assert!(search("charlie").is_empty());
+        db.purge_trash(None, ChangeCause::User).unwrap();
+        assert!(search("charlie").is_empty());
+        assert!(search("crimson").is_empty());
+        let keys: i64 = db.conn.query_row("SELECT COUNT(*) FROM event_search", [], |row| row.get(0)).unwrap();
+        assert_eq!(keys, 1);
+        db.conn.execute("INSERT INTO events_fts (events_fts) VALUES ('integrity-check')", []).unwrap();
+    }
+
+    #[test]
+    fn pages_do_not_skip_or_repeat_rows_with_equal_sort_keys() {
+        let db = Database::new(":memory:").unwrap();
+        for (name, priority) in [("A", "1"), ("B", "1"), ("A", "1"), ("C", "2"), ("A", ""), ("B", "1"), ("A", "1")] {
+            let mut tags = HashMap::new();
+            if !priority.is_empty() {
+                tags.insert("priority".to_string(), priority.to_string());
+            }
+            db.create_event(CreateEventRequest {
+                name: name.to_string(),
+                description: String::new(),
                 tags,
-                status,
-                created_at: DateTime::parse_from_rfc3339(&created_at_str).unwrap().with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&updated_at_str).unwrap().with_timezone(&Utc),
-                dependencies,
+                dependencies: vec![],
+                due_at: None,
+                start_after: None,
+                estimate_minutes: None,
             })
-        })?;
+            .unwrap();
+        }
+        // 所有事件的创建时间相同，只能靠 id 区分
+        db.conn.execute("UPDATE events SET created_at = '2024-01-01T00:00:00+00:00'", []).unwrap();
 
-        let mut events = Vec::new();
-        for event in event_iter {
-            let event = event?;
-            
-            // Filter by tags if specified
-            if let Some(ref filter_tags) = filter.tags {
-                let mut matches = true;
-                for (key, value) in filter_tags {
-                    if let Some(event_value) = event.tags.get(key) {
-                        if event_value != value {
-                            matches = false;
-                            break;
-                        }
-                    } else {
-                        matches = false;
-                        break;
-                    }
-                }
-                if matches {
-                    events.push(event);
+        let sort = |key: &str| SortSpec {
+            keys: vec![
+                SortKey { field: SortField::Tag { key: key.to_string() }, direction: SortDirection::Asc },
+                SortKey { field: SortField::Name, direction: SortDirection::Asc },
+            ],
+        };
+        for filter in [
+            EventFilter::default(),
+            EventFilter { sort: Some(sort("priority")), ..EventFilter::default() },
+        ] {
+            let all = db.filter_events(filter.clone(), PageRequest::default()).unwrap();
+            let expected: Vec<String> = all.items.iter().map(|event| event.id.clone()).collect();
+            assert_eq!(expected.len(), 7);
+
+            let mut paged = Vec::new();
+            let mut cursor = None;
+            loop {
+                let page = db.filter_events(filter.clone(), PageRequest { limit: Some(2), cursor }).unwrap();
+                assert_eq!(page.total, 7);
+                assert!(page.items.len() <= 2);
+                paged.extend(page.items.into_iter().map(|event| event.id));
+                cursor = page.next_cursor;
+                if cursor.is_none() {
+                    break;
                 }
-            } else {
-                events.push(event);
             }
+            assert_eq!(paged, expected);
         }
-        Ok(events)
+
+        // 按另一个标签排序时不接受之前的游标
+        let filter = EventFilter { sort: Some(sort("priority")), ..EventFilter::default() };
+        let cursor = db.filter_events(filter, PageRequest { limit: Some(2), cursor: None }).unwrap().next_cursor;
+        let other = EventFilter { sort: Some(sort("owner")), ..EventFilter::default() };
+        let error = db.filter_events(other, PageRequest { limit: Some(2), cursor }).unwrap_err();
+        assert_eq!(error.code(), "Validation");
+    }
+
+    #[test]
+    fn corrupt_journal_fails_undo_without_marking_it_undone() {
+        let db = Database::new(":memory:").unwrap();
+        create(&db, "A", vec![]);
+        db.conn.execute("UPDATE operations SET before = 'not json'", []).unwrap();
+
+        assert!(db.undo().is_err());
+        let undone: i64 = db.conn.query_row("SELECT undone FROM operations", [], |row| row.get(0)).unwrap();
+        assert_eq!(undone, 0);
+        assert_eq!(db.get_all_events().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn corrupt_view_filter_is_an_error() {
+        let db = Database::new(":memory:").unwrap();
+        let view = db
+            .create_view(CreateViewRequest { name: "Mine".to_string(), filter: EventFilter::default() })
+            .unwrap();
+        db.conn.execute("UPDATE views SET filter = '{' WHERE id = ?1", [&view.id]).unwrap();
+
+        assert_eq!(db.get_view(&view.id).unwrap_err().code(), "Validation");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn corrupt_tags_and_history_are_errors() {
+        let db = Database::new(":memory:").unwrap();
+        let event = create(&db, "A", vec![]);
+        // 历史只能追加，插入一条损坏的记录
+        db.conn
+            .execute(
+                "INSERT INTO event_history (event_id, action, cause, changes, recorded_at)
+                 SELECT event_id, action, cause, '[', recorded_at FROM event_history WHERE event_id = ?1",
+                [&event.id],
+            )
+            .unwrap();
+        assert_eq!(db.get_event_history(&event.id).unwrap_err().code(), "Validation");
+
+        db.conn.execute("UPDATE events SET tags = '{\"size\": 1}' WHERE id = ?1", [&event.id]).unwrap();
+        assert_eq!(db.get_event(&event.id).unwrap_err().code(), "Validation");
+        assert_eq!(db.get_all_events().unwrap_err().code(), "Validation");
+    }
+
+    #[test]
+    fn detects_unc_paths() {
+        assert!(is_network_path(Path::new(r"\\server\share\todo.db")));
+        assert!(is_network_path(Path::new(r"\\?\UNC\server\share\todo.db")));
+        assert!(!is_network_path(Path::new(r"\\?\C:\data\todo.db")));
+        assert!(!is_network_path(Path::new(r"C:\data\todo.db")));
+        assert!(!is_network_path(Path::new("/home/user/todo.db")));
+    }
+}
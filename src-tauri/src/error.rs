@@ -0,0 +1,158 @@
+use crate::graph::DependencyError;
+use crate::query::QueryError;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
+
+// 数据库和命令统一使用的错误类型。
+// 序列化为 { code, message, details }，前端根据稳定的 code 区分错误类型，
+// details 中是各类错误的结构化信息
+#[derive(Debug)]
+pub enum AppError {
+    // 请求的事件、视图或数据库文件不存在
+    NotFound { entity: &'static str, id: String },
+    // 输入或数据库中保存的数据不合法；field 为出错的字段，position 为查询文本中的字符位置
+    Validation { message: String, field: Option<&'static str>, position: Option<usize> },
+    // 请求与现有数据冲突，例如循环依赖、仍被依赖的事件、重名的视图；ids 为涉及的记录
+    Conflict { message: String, ids: Vec<String> },
+    Io { message: String, path: Option<String> },
+    Sqlite(rusqlite::Error),
+    // 数据库由更新版本的应用创建
+    Schema { version: i64, supported: i64 },
+    // 应用内部的故障，例如锁被中毒或后台任务崩溃，与用户输入和文件无关
+    Internal { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, AppError>;
+
+impl AppError {
+    pub fn not_found(entity: &'static str, id: impl Into<String>) -> Self {
+        AppError::NotFound { entity, id: id.into() }
+    }
+
+    pub fn validation(field: &'static str, message: impl Into<String>) -> Self {
+        AppError::Validation { message: message.into(), field: Some(field), position: None }
+    }
+
+    pub fn conflict(message: impl Into<String>, ids: Vec<String>) -> Self {
+        AppError::Conflict { message: message.into(), ids }
+    }
+
+    pub fn io(path: impl Into<String>, error: std::io::Error) -> Self {
+        AppError::Io { message: error.to_string(), path: Some(path.into()) }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound { .. } => "NotFound",
+            AppError::Validation { .. } => "Validation",
+            AppError::Conflict { .. } => "Conflict",
+            AppError::Io { .. } => "Io",
+            AppError::Sqlite(_) => "Sqlite",
+            AppError::Schema { .. } => "Schema",
+            AppError::Internal { .. } => "Internal",
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            AppError::NotFound { entity, id } => json!({ "entity": entity, "id": id }),
+            AppError::Validation { field, position, .. } => json!({ "field": field, "position": position }),
+            AppError::Conflict { ids, .. } => json!({ "ids": ids }),
+            AppError::Io { path, .. } => json!({ "path": path }),
+            // SQLite 的扩展错误码，例如数据库被锁定时为 DatabaseBusy
+            AppError::Sqlite(rusqlite::Error::SqliteFailure(error, _)) => {
+                json!({ "sqlite_code": format!("{:?}", error.code), "extended_code": error.extended_code })
+            }
+            AppError::Sqlite(_) => json!({}),
+            AppError::Schema { version, supported } => json!({ "version": version, "supported": supported }),
+            AppError::Internal { .. } => json!({}),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound { entity, id } => write!(f, "{} not found: {}", capitalize(entity), id),
+            AppError::Validation { message, .. } => write!(f, "{}", message),
+            AppError::Conflict { message, .. } => write!(f, "{}", message),
+            AppError::Io { message, path: Some(path) } => write!(f, "{}: {}", path, message),
+            AppError::Io { message, path: None } => write!(f, "{}", message),
+            AppError::Sqlite(error) => write!(f, "Database error: {}", error),
+            AppError::Schema { version, supported } => write!(
+                f,
+                "Database schema version {} is newer than this app supports (version {}). Please update Todo Scheduler.",
+                version, supported
+            ),
+            AppError::Internal { message } => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            // 保存的 JSON 无法解析（见 database.rs 的 from_json）
+            rusqlite::Error::FromSqlConversionFailure(column, _, ref cause) if cause.is::<serde_json::Error>() => {
+                AppError::Validation {
+                    message: format!("Stored data in column {} is not valid JSON: {}", column, cause),
+                    field: None,
+                    position: None,
+                }
+            }
+            error => AppError::Sqlite(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        AppError::Io { message: error.to_string(), path: None }
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        AppError::Internal { message: format!("Lock error: {}", error) }
+    }
+}
+
+impl From<DependencyError> for AppError {
+    fn from(error: DependencyError) -> Self {
+        match &error {
+            DependencyError::SelfReference(_) | DependencyError::UnknownDependency(_) => {
+                AppError::validation("dependencies", error.to_string())
+            }
+            DependencyError::Cycle(path) => {
+                AppError::conflict(error.to_string(), path.iter().map(|node| node.id.clone()).collect())
+            }
+        }
+    }
+}
+
+impl From<QueryError> for AppError {
+    fn from(error: QueryError) -> Self {
+        AppError::Validation { message: error.to_string(), field: Some("query"), position: Some(error.position) }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
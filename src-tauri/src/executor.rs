@@ -0,0 +1,302 @@
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use crate::migrations::LATEST_VERSION;
+use crate::models::DatabaseStatus;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use uuid::Uuid;
+
+// 读连接池的大小
+const READ_POOL_SIZE: usize = 4;
+
+// 在阻塞线程池中执行数据库操作，避免同步的 SQLite 调用占用异步运行时。
+// 写操作通过唯一的写连接串行执行；读操作使用 WAL 模式下的只读连接池，
+// 可以与写操作和其他读操作并发执行。
+// 同一个数据库文件同时只有一个实例可以写入（instance_lock 表），
+// 其他实例以只读方式打开，持有者的心跳过期后自动接管
+pub struct DbExecutor {
+    pool: RwLock<Arc<Pool>>,
+    // 本进程的实例 ID，切换数据库时保持不变
+    instance_id: String,
+}
+
+struct Pool {
+    writer: Mutex<Database>,
+    readers: Vec<Mutex<Database>>,
+    next_reader: AtomicUsize,
+    status: Mutex<DatabaseStatus>,
+    instance_id: String,
+    // 本连接池最后写入的心跳时间，没有持有写锁时为 None。
+    // 切换到同一个文件时新连接池会写入新的心跳，旧连接池释放时不会删除它
+    heartbeat_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl DbExecutor {
+    pub fn open(path: &str) -> Result<Self> {
+        let instance_id = Uuid::new_v4().to_string();
+        let pool = Pool::open(path, instance_id.clone())?;
+        Ok(DbExecutor { pool: RwLock::new(Arc::new(pool)), instance_id })
+    }
+
+    // 在一个只读快照中执行查询
+    pub async fn read<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool()?;
+        blocking(move || {
+            let db = pool.reader()?;
+            db.snapshot(f)
+        })
+        .await
+    }
+
+    // 只读模式下拒绝写操作
+    pub async fn write<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool()?;
+        blocking(move || {
+            let db = pool.writer.lock()?;
+            pool.check_writable()?;
+            f(&db)
+        })
+        .await
+    }
+
+    // 打开另一个数据库并替换当前的连接；正在执行的操作仍使用原来的连接完成
+    pub async fn switch(&self, path: String) -> Result<()> {
+        let instance_id = self.instance_id.clone();
+        let pool = blocking(move || Pool::open(&path, instance_id)).await?;
+        *self.pool.write()? = Arc::new(pool);
+        Ok(())
+    }
+
+    pub fn status(&self) -> Result<DatabaseStatus> {
+        Ok(self.pool()?.status.lock()?.clone())
+    }
+
+    // 续期写锁；只读模式下如果持有者的心跳已过期则接管写锁
+    pub async fn heartbeat(&self) -> Result<DatabaseStatus> {
+        let pool = self.pool()?;
+        blocking(move || pool.heartbeat()).await
+    }
+
+    // 退出时释放写锁，使其他实例可以立即写入
+    pub fn release(&self) -> Result<()> {
+        self.pool()?.release()
+    }
+
+    fn pool(&self) -> Result<Arc<Pool>> {
+        Ok(self.pool.read()?.clone())
+    }
+}
+
+// 启动时打开的数据库；requested 无法打开时回退到默认数据库，warning 说明原因
+pub struct StartupDatabase {
+    pub executor: DbExecutor,
+    pub path: String,
+    pub warning: Option<String>,
+}
+
+pub fn open_startup_database(requested: Option<&str>, default_path: &str) -> Result<StartupDatabase> {
+    let warning = match requested.filter(|path| *path != default_path) {
+        None => None,
+        Some(path) => {
+            let opened = if std::path::Path::new(path).exists() {
+                Database::validate(path).and_then(|_| DbExecutor::open(path))
+            } else {
+                Err(AppError::not_found("database", path))
+            };
+            match opened {
+                Ok(executor) => return Ok(StartupDatabase { executor, path: path.to_string(), warning: None }),
+                Err(e) => Some(format!("Could not open {} ({}). Opened the default database instead.", path, e)),
+            }
+        }
+    };
+    if let Some(warning) = &warning {
+        eprintln!("⚠️ {}", warning);
+    }
+    Ok(StartupDatabase { executor: DbExecutor::open(default_path)?, path: default_path.to_string(), warning })
+}
+
+impl Pool {
+    fn open(path: &str, instance_id: String) -> Result<Self> {
+        // 写连接在获取写锁后负责迁移（见 heartbeat）
+        let writer = Database::open(path)?;
+        // 内存数据库无法在连接之间共享，读操作直接使用写连接
+        let readers = if path == ":memory:" {
+            Vec::new()
+        } else {
+            (0..READ_POOL_SIZE)
+                .map(|_| Database::open_reader(path).map(Mutex::new))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let pool = Pool {
+            writer: Mutex::new(writer),
+            readers,
+            next_reader: AtomicUsize::new(0),
+            status: Mutex::new(DatabaseStatus { read_only: false, locked_by: None }),
+            instance_id,
+            heartbeat_at: Mutex::new(None),
+        };
+        let status = pool.heartbeat()?;
+        // 持有写锁的实例还没有升级结构（旧版本的应用），只读连接无法使用旧结构
+        if let Some(holder) = status.locked_by {
+            if pool.writer.lock()?.schema_version()? < LATEST_VERSION {
+                return Err(AppError::conflict(
+                    format!(
+                        "The database is open in an older version of the app ({}, pid {}); close it there first",
+                        holder.host, holder.pid
+                    ),
+                    vec![holder.instance_id],
+                ));
+            }
+        }
+        Ok(pool)
+    }
+
+    // 优先使用空闲的读连接，都在使用中时按轮转顺序等待
+    fn reader(&self) -> Result<MutexGuard<'_, Database>> {
+        if self.readers.is_empty() {
+            return Ok(self.writer.lock()?);
+        }
+        for reader in &self.readers {
+            if let Ok(guard) = reader.try_lock() {
+                return Ok(guard);
+            }
+        }
+        let index = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+        Ok(self.readers[index].lock()?)
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        match &self.status.lock()?.locked_by {
+            Some(holder) => Err(AppError::conflict(
+                format!(
+                    "The database is open for writing in another instance ({}, pid {}); changes are disabled until it closes",
+                    holder.host, holder.pid
+                ),
+                vec![holder.instance_id.clone()],
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn heartbeat(&self) -> Result<DatabaseStatus> {
+        let now = Utc::now();
+        let writer = self.writer.lock()?;
+        let mut holder = writer.acquire_instance_lock(&self.instance_id, &host_name(), std::process::id(), now)?;
+        // 只有持有写锁的实例升级结构；旧数据库在升级前没有锁表，升级后重新获取
+        if holder.is_none() && writer.migrate()? {
+            holder = writer.acquire_instance_lock(&self.instance_id, &host_name(), std::process::id(), now)?;
+        }
+        drop(writer);
+        *self.heartbeat_at.lock()? = if holder.is_none() { Some(now) } else { None };
+        let mut status = self.status.lock()?;
+        *status = DatabaseStatus { read_only: holder.is_some(), locked_by: holder };
+        Ok(status.clone())
+    }
+
+    fn release(&self) -> Result<()> {
+        match self.heartbeat_at.lock()?.take() {
+            Some(heartbeat_at) => self.writer.lock()?.release_instance_lock(&self.instance_id, heartbeat_at),
+            None => Ok(()),
+        }
+    }
+}
+
+// 切换数据库时旧的连接被丢弃，同时释放它持有的写锁
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if let Err(e) = self.release() {
+            eprintln!("🔒 Failed to release database lock: {}", e);
+        }
+    }
+}
+
+// 在阻塞线程池中执行一个同步操作
+pub async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::Internal { message: format!("Database task failed: {}", e) })?
+}
+
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown host".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn lock_rows(path: &std::path::Path) -> i64 {
+        let conn = Connection::open(path).unwrap();
+        conn.query_row("SELECT COUNT(*) FROM instance_lock", [], |row| row.get(0)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn switching_to_the_open_database_keeps_the_lock() {
+        let dir = std::env::temp_dir().join(format!("todo-scheduler-executor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("switch.db");
+        let _ = std::fs::remove_file(&path);
+        let path_str = path.to_string_lossy().to_string();
+
+        let executor = DbExecutor::open(&path_str).unwrap();
+        executor.switch(path_str.clone()).await.unwrap();
+        assert_eq!(lock_rows(&path), 1);
+        assert!(!executor.status().unwrap().read_only);
+
+        // 另一个实例仍然只能以只读方式打开
+        let other = DbExecutor::open(&path_str).unwrap();
+        assert!(other.status().unwrap().read_only);
+        drop(other);
+        assert_eq!(lock_rows(&path), 1);
+
+        executor.release().unwrap();
+        assert_eq!(lock_rows(&path), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn read_only_instance_leaves_the_file_unchanged() {
+        let dir = std::env::temp_dir().join(format!("todo-scheduler-executor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("locked.db");
+        let _ = std::fs::remove_file(&path);
+        let path_str = path.to_string_lossy().to_string();
+
+        // 另一台主机上的实例持有写锁
+        {
+            let db = Database::new(&path).unwrap();
+            assert!(db.acquire_instance_lock("other", "other host", 1, chrono::Utc::now()).unwrap().is_none());
+        }
+
+        let executor = DbExecutor::open(&path_str).unwrap();
+        assert!(executor.status().unwrap().read_only);
+        assert!(executor.read(|db| db.get_all_events()).await.unwrap().is_empty());
+        let error = executor.write(|db| db.set_trash_retention_days(7)).await.unwrap_err();
+        assert_eq!(error.code(), "Conflict");
+        drop(executor);
+
+        let conn = Connection::open(&path).unwrap();
+        let holder: String = conn.query_row("SELECT instance_id FROM instance_lock", [], |row| row.get(0)).unwrap();
+        assert_eq!(holder, "other");
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, LATEST_VERSION);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
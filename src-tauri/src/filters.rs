@@ -0,0 +1,589 @@
+use crate::database::status_to_str;
+use crate::query::{self, QueryError};
+use crate::models::{EventFilter, TagPredicate, SortSpec, SortField, SortDirection};
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// 参数化的 SQL 条件片段，events 表的别名固定为 e
+#[derive(Debug, Clone)]
+pub struct SqlCondition {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl SqlCondition {
+    pub fn new(sql: impl Into<String>, params: Vec<Value>) -> Self {
+        Self { sql: sql.into(), params }
+    }
+
+    pub fn negate(self) -> Self {
+        Self {
+            sql: format!("NOT ({})", self.sql),
+            params: self.params,
+        }
+    }
+
+    pub fn and(conditions: Vec<SqlCondition>) -> Self {
+        Self::join(conditions, " AND ", "1=1")
+    }
+
+    pub fn or(conditions: Vec<SqlCondition>) -> Self {
+        Self::join(conditions, " OR ", "0=1")
+    }
+
+    fn join(conditions: Vec<SqlCondition>, separator: &str, empty: &str) -> Self {
+        if conditions.is_empty() {
+            return Self::new(empty, Vec::new());
+        }
+        let sql = conditions
+            .iter()
+            .map(|condition| format!("({})", condition.sql))
+            .collect::<Vec<_>>()
+            .join(separator);
+        let params = conditions.into_iter().flat_map(|condition| condition.params).collect();
+        Self { sql, params }
+    }
+}
+
+// 一个排序表达式，表达式本身不会为 NULL
+#[derive(Debug, Clone)]
+pub struct OrderTerm {
+    pub expr: String,
+    pub descending: bool,
+}
+
+// 排序所需的 JOIN 子句及参数和排序表达式列表
+#[derive(Debug, Clone)]
+pub struct SqlOrder {
+    pub joins: String,
+    pub params: Vec<Value>,
+    pub terms: Vec<OrderTerm>,
+}
+
+impl SqlOrder {
+    pub fn order_by(&self) -> String {
+        self.terms
+            .iter()
+            .map(|term| format!("{} {}", term.expr, if term.descending { "DESC" } else { "ASC" }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // 排序表达式作为额外的查询列，用于生成下一页的游标
+    pub fn select_terms(&self) -> String {
+        self.terms.iter().map(|term| format!(", {}", term.expr)).collect()
+    }
+
+    // 键集分页条件：排在游标对应的行之后的行。
+    // 展开为 (t1 > v1) OR (t1 = v1 AND t2 > v2) OR ...，每个键按自己的方向比较
+    pub fn after(&self, values: &[Value]) -> SqlCondition {
+        let mut branches = Vec::new();
+        for (i, term) in self.terms.iter().enumerate() {
+            let mut sql = String::new();
+            let mut params = Vec::new();
+            for (previous, value) in self.terms[..i].iter().zip(values) {
+                sql.push_str(&format!("{} = ? AND ", previous.expr));
+                params.push(value.clone());
+            }
+            let operator = if term.descending { "<" } else { ">" };
+            sql.push_str(&format!("{} {} ?", term.expr, operator));
+            params.push(values[i].clone());
+            branches.push(SqlCondition::new(sql, params));
+        }
+        SqlCondition::or(branches)
+    }
+
+    // 游标只对生成它的过滤条件和排序方式（包括排序的标签键）有效。
+    // 过滤条件先转换为 serde_json::Value，对象的键是有序的，相同的条件总是得到相同的文本
+    fn fingerprint(&self, filter: &EventFilter) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.order_by().hash(&mut hasher);
+        format!("{:?}", self.params).hash(&mut hasher);
+        serde_json::to_value(filter)
+            .map(|filter| filter.to_string())
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // 游标是排序值的 JSON 的十六进制编码，对前端不透明
+    pub fn encode_cursor(&self, filter: &EventFilter, values: &[Value]) -> String {
+        let values: Vec<serde_json::Value> = values
+            .iter()
+            .map(|value| match value {
+                Value::Integer(i) => serde_json::json!(i),
+                Value::Real(f) => serde_json::json!(f),
+                Value::Text(text) => serde_json::json!(text),
+                Value::Null | Value::Blob(_) => serde_json::Value::Null,
+            })
+            .collect();
+        let json = serde_json::json!({ "order": self.fingerprint(filter), "values": values }).to_string();
+        json.bytes().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode_cursor(&self, filter: &EventFilter, cursor: &str) -> Option<Vec<Value>> {
+        if !cursor.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let json: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+        if json.get("order")?.as_u64()? != self.fingerprint(filter) {
+            return None;
+        }
+        let values = json
+            .get("values")?
+            .as_array()?
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::Number(n) if n.is_i64() => n.as_i64().map(Value::Integer),
+                serde_json::Value::Number(n) => n.as_f64().map(Value::Real),
+                serde_json::Value::String(text) => Some(Value::Text(text.clone())),
+                _ => None,
+            })
+            .collect::<Option<Vec<Value>>>()?;
+        (values.len() == self.terms.len()).then_some(values)
+    }
+}
+
+// 把排序规则编译为 ORDER BY；没有规则时按创建时间倒序。
+// 最后总是追加 id 作为决胜键，保证相同排序值的事件顺序稳定
+pub fn sort_order(spec: Option<&SortSpec>) -> SqlOrder {
+    let mut order = SqlOrder {
+        joins: String::new(),
+        params: Vec::new(),
+        terms: Vec::new(),
+    };
+    let asc = |expr: String| OrderTerm { expr, descending: false };
+    let directed = |expr: String, direction: SortDirection| OrderTerm {
+        expr,
+        descending: direction == SortDirection::Desc,
+    };
+
+    let keys = spec.map(|spec| spec.keys.as_slice()).unwrap_or_default();
+    for (i, key) in keys.iter().enumerate() {
+        match &key.field {
+            SortField::Tag { key: tag_key } => {
+                let alias = format!("sort_tag{}", i);
+                order.joins.push_str(&format!(
+                    " LEFT JOIN event_tags {alias} ON {alias}.event_id = e.id AND {alias}.key = ?"
+                ));
+                order.params.push(Value::Text(tag_key.clone()));
+                order.terms.push(asc(format!("({alias}.value IS NULL)")));
+                order.terms.push(asc(format!("({alias}.num_value IS NULL)")));
+                order.terms.push(directed(format!("COALESCE({alias}.num_value, 0)"), key.direction));
+                order.terms.push(directed(format!("COALESCE({alias}.value, '') COLLATE NOCASE"), key.direction));
+            }
+            SortField::Name => {
+                order.terms.push(directed("e.name COLLATE NOCASE".to_string(), key.direction));
+            }
+            SortField::Created => {
+                order.terms.push(directed("e.created_at".to_string(), key.direction));
+            }
+            SortField::Updated => {
+                order.terms.push(directed("e.updated_at".to_string(), key.direction));
+            }
+            SortField::Due => {
+                order.terms.push(asc("(e.due_at IS NULL)".to_string()));
+                order.terms.push(directed("COALESCE(e.due_at, '')".to_string(), key.direction));
+            }
+            SortField::Status => {
+                order.terms.push(directed(
+                    "CASE e.status WHEN 'pending' THEN 0 WHEN 'in_progress' THEN 1 WHEN 'blocked' THEN 2 ELSE 3 END"
+                        .to_string(),
+                    key.direction,
+                ));
+            }
+        }
+    }
+
+    if keys.is_empty() {
+        order.terms.push(OrderTerm { expr: "e.created_at".to_string(), descending: true });
+    }
+    order.terms.push(OrderTerm { expr: "e.id".to_string(), descending: true });
+    order
+}
+
+// 把 EventFilter 编译为 WHERE 条件，查询文本有语法错误时返回错误位置
+pub fn filter_condition(filter: &EventFilter, now: DateTime<Utc>) -> Result<SqlCondition, QueryError> {
+    let mut conditions = Vec::new();
+
+    if let Some(status) = filter.status {
+        conditions.push(SqlCondition::new(
+            "e.status = ?",
+            vec![Value::Text(status_to_str(status).to_string())],
+        ));
+    }
+
+    if let Some(search) = &filter.search {
+        if !search.is_empty() {
+            conditions.push(text_contains_condition(search));
+        }
+    }
+
+    if let Some(tags) = &filter.tags {
+        let mut tags: Vec<_> = tags.iter().collect();
+        tags.sort();
+        for (key, value) in tags {
+            conditions.push(tag_condition(&TagPredicate::In {
+                key: key.clone(),
+                values: vec![value.clone()],
+            }));
+        }
+    }
+
+    for predicate in filter.tag_predicates.iter().flatten() {
+        conditions.push(tag_condition(predicate));
+    }
+
+    if let Some(due_before) = filter.due_before {
+        conditions.push(SqlCondition::new("e.due_at < ?", vec![Value::Text(due_before.to_rfc3339())]));
+    }
+
+    if let Some(due_after) = filter.due_after {
+        conditions.push(SqlCondition::new("e.due_at >= ?", vec![Value::Text(due_after.to_rfc3339())]));
+    }
+
+    if let Some(deferred) = filter.deferred {
+        let sql = if deferred {
+            "e.start_after > ?"
+        } else {
+            "e.start_after IS NULL OR e.start_after <= ?"
+        };
+        conditions.push(SqlCondition::new(sql, vec![Value::Text(now.to_rfc3339())]));
+    }
+
+    if let Some(text) = &filter.query {
+        if let Some(expr) = query::parse(text, now)? {
+            conditions.push(query::compile(&expr, now));
+        }
+    }
+
+    Ok(SqlCondition::and(conditions))
+}
+
+// 名称或描述包含给定文本（按字面匹配，不区分大小写）
+pub fn text_contains_condition(text: &str) -> SqlCondition {
+    let pattern = Value::Text(format!("%{}%", escape_like(text)));
+    SqlCondition::new(
+        "e.name LIKE ? ESCAPE '\\' OR e.description LIKE ? ESCAPE '\\'",
+        vec![pattern.clone(), pattern],
+    )
+}
+
+// 标签条件通过 event_tags 表上的 (key, value) / (key, num_value) 索引求值
+pub fn tag_condition(predicate: &TagPredicate) -> SqlCondition {
+    const TAG_EXISTS: &str = "EXISTS (SELECT 1 FROM event_tags t WHERE t.event_id = e.id AND t.key = ?";
+
+    match predicate {
+        TagPredicate::Exists { key } => {
+            SqlCondition::new(format!("{})", TAG_EXISTS), vec![Value::Text(key.clone())])
+        }
+        TagPredicate::Missing { key } => {
+            SqlCondition::new(format!("{})", TAG_EXISTS), vec![Value::Text(key.clone())]).negate()
+        }
+        TagPredicate::In { key, values } => {
+            if values.is_empty() {
+                return SqlCondition::new("0=1", Vec::new());
+            }
+            let placeholders = vec!["?"; values.len()].join(", ");
+            let mut params = vec![Value::Text(key.clone())];
+            params.extend(values.iter().map(|value| Value::Text(value.clone())));
+            SqlCondition::new(format!("{} AND t.value IN ({}))", TAG_EXISTS, placeholders), params)
+        }
+        TagPredicate::Prefix { key, prefix } => SqlCondition::new(
+            format!("{} AND t.value GLOB ?)", TAG_EXISTS),
+            vec![Value::Text(key.clone()), Value::Text(format!("{}*", escape_glob(prefix)))],
+        ),
+        TagPredicate::Range { key, min, max } => {
+            let mut sql = format!("{} AND t.num_value IS NOT NULL", TAG_EXISTS);
+            let mut params = vec![Value::Text(key.clone())];
+            if let Some(min) = min {
+                sql.push_str(" AND t.num_value >= ?");
+                params.push(Value::Real(*min));
+            }
+            if let Some(max) = max {
+                sql.push_str(" AND t.num_value <= ?");
+                params.push(Value::Real(*max));
+            }
+            sql.push(')');
+            SqlCondition::new(sql, params)
+        }
+    }
+}
+
+// 转义 LIKE 通配符，使用户输入的 % 和 _ 按字面匹配
+pub fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// GLOB 没有转义字符，特殊字符用方括号包起来按字面匹配
+pub fn escape_glob(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '*' | '?' | '[' => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+// 把用户输入转换为 FTS5 查询：每个词都加引号避免语法错误，
+// 引号内的内容作为短语，以 * 结尾的词作为前缀查询，多个条件之间为 AND
+pub fn fts_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' {
+            chars.next();
+            let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            if !phrase.trim().is_empty() {
+                terms.push(format!("\"{}\"", phrase.trim().replace('"', "\"\"")));
+            }
+            continue;
+        }
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '"' {
+                break;
+            }
+            word.push(c);
+            chars.next();
+        }
+        let (word, prefix) = match word.strip_suffix('*') {
+            Some(stem) => (stem.trim_end_matches('*'), true),
+            None => (word.as_str(), false),
+        };
+        if word.is_empty() {
+            continue;
+        }
+        terms.push(format!("\"{}\"{}", word.replace('"', "\"\""), if prefix { "*" } else { "" }));
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::models::{CreateEventRequest, PageRequest, SortKey};
+    use std::collections::HashMap;
+
+    fn tag_sort(key: &str) -> SortSpec {
+        SortSpec {
+            keys: vec![
+                SortKey { field: SortField::Tag { key: key.to_string() }, direction: SortDirection::Asc },
+                SortKey { field: SortField::Name, direction: SortDirection::Desc },
+            ],
+        }
+    }
+
+    fn sorted_filter(key: &str) -> EventFilter {
+        EventFilter { sort: Some(tag_sort(key)), ..EventFilter::default() }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let filter = sorted_filter("priority");
+        let order = sort_order(filter.sort.as_ref());
+        let values = vec![
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Real(2.5),
+            Value::Text("High".to_string()),
+            Value::Text("Write \"docs\"".to_string()),
+            Value::Text("b3c1".to_string()),
+        ];
+        assert_eq!(values.len(), order.terms.len());
+
+        let cursor = order.encode_cursor(&filter, &values);
+        assert!(cursor.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(order.decode_cursor(&filter, &cursor), Some(values));
+    }
+
+    #[test]
+    fn cursor_is_bound_to_the_filter_and_sort() {
+        let filter = sorted_filter("a");
+        let order = sort_order(filter.sort.as_ref());
+        let values: Vec<Value> = (0..order.terms.len() as i64).map(Value::Integer).collect();
+        let cursor = order.encode_cursor(&filter, &values);
+
+        // 同样的排序结构但排序的标签不同
+        let other_filter = sorted_filter("b");
+        let other_order = sort_order(other_filter.sort.as_ref());
+        assert_eq!(order.order_by(), other_order.order_by());
+        assert_eq!(other_order.decode_cursor(&other_filter, &cursor), None);
+
+        // 同样的排序但过滤条件不同
+        let narrowed = EventFilter { search: Some("docs".to_string()), ..filter.clone() };
+        assert_eq!(order.decode_cursor(&narrowed, &cursor), None);
+
+        // 标签条件中 HashMap 的顺序不影响游标
+        let tags = |pairs: &[(&str, &str)]| {
+            Some(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+        };
+        let first = EventFilter { tags: tags(&[("x", "1"), ("y", "2"), ("z", "3")]), ..filter.clone() };
+        let second = EventFilter { tags: tags(&[("z", "3"), ("y", "2"), ("x", "1")]), ..filter.clone() };
+        let cursor = order.encode_cursor(&first, &values);
+        assert!(order.decode_cursor(&second, &cursor).is_some());
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        let filter = EventFilter::default();
+        let order = sort_order(None);
+        assert_eq!(order.decode_cursor(&filter, "abc"), None);
+        assert_eq!(order.decode_cursor(&filter, "zz"), None);
+        assert_eq!(order.decode_cursor(&filter, "7b7d"), None);
+
+        // 值的数量与排序键不一致
+        let cursor = order.encode_cursor(&filter, &[Value::Text("2024-01-01".to_string())]);
+        assert_eq!(order.decode_cursor(&filter, &cursor), None);
+    }
+
+    #[test]
+    fn after_compares_each_key_in_its_own_direction() {
+        let order = sort_order(None);
+        let condition = order.after(&[Value::Text("t".to_string()), Value::Text("id".to_string())]);
+        assert_eq!(condition.sql, "(e.created_at < ?) OR (e.created_at = ? AND e.id < ?)");
+        assert_eq!(condition.params.len(), 3);
+    }
+
+    fn create(db: &Database, name: &str, tags: &[(&str, &str)]) {
+        db.create_event(CreateEventRequest {
+            name: name.to_string(),
+            description: String::new(),
+            tags: tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+            dependencies: vec![],
+            due_at: None,
+            start_after: None,
+            estimate_minutes: None,
+        })
+        .unwrap();
+    }
+
+    fn tagged_events() -> Database {
+        let db = Database::new(":memory:").unwrap();
+        create(&db, "A", &[("size", "10"), ("code", "50%_off")]);
+        create(&db, "B", &[("size", "9"), ("code", "50% off")]);
+        create(&db, "C", &[("code", "a*b[1]")]);
+        create(&db, "D", &[("size", "large"), ("code", "a*bc")]);
+        create(&db, "E", &[]);
+        db
+    }
+
+    // 满足标签条件的事件名称，按名称排序
+    fn matching(db: &Database, predicate: TagPredicate) -> Vec<String> {
+        let filter = EventFilter { tag_predicates: Some(vec![predicate]), ..EventFilter::default() };
+        let mut names: Vec<String> = db
+            .filter_events(filter, PageRequest::default())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|event| event.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn prefix(prefix: &str) -> TagPredicate {
+        TagPredicate::Prefix { key: "code".to_string(), prefix: prefix.to_string() }
+    }
+
+    fn range(min: Option<f64>, max: Option<f64>) -> TagPredicate {
+        TagPredicate::Range { key: "size".to_string(), min, max }
+    }
+
+    #[test]
+    fn tag_exists_and_missing() {
+        let db = tagged_events();
+        assert_eq!(matching(&db, TagPredicate::Exists { key: "size".to_string() }), ["A", "B", "D"]);
+        assert_eq!(matching(&db, TagPredicate::Missing { key: "size".to_string() }), ["C", "E"]);
+        assert!(matching(&db, TagPredicate::Exists { key: "owner".to_string() }).is_empty());
+    }
+
+    #[test]
+    fn tag_in_values() {
+        let db = tagged_events();
+        let values = |values: &[&str]| TagPredicate::In {
+            key: "size".to_string(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+        };
+        assert_eq!(matching(&db, values(&["9", "large"])), ["B", "D"]);
+        assert!(matching(&db, values(&["10.0"])).is_empty());
+        assert!(matching(&db, values(&[])).is_empty());
+    }
+
+    #[test]
+    fn tag_prefix_matches_special_characters_literally() {
+        let db = tagged_events();
+        assert_eq!(matching(&db, prefix("50%")), ["A", "B"]);
+        assert_eq!(matching(&db, prefix("50%_")), ["A"]);
+        assert_eq!(matching(&db, prefix("a*")), ["C", "D"]);
+        assert_eq!(matching(&db, prefix("a*b[")), ["C"]);
+        assert!(matching(&db, prefix("a?")).is_empty());
+        assert!(matching(&db, prefix("_")).is_empty());
+    }
+
+    #[test]
+    fn tag_range_compares_numerically() {
+        let db = tagged_events();
+        // 文本比较时 "10" < "9"，数值比较时 10 > 9
+        assert_eq!(matching(&db, range(Some(9.5), None)), ["A"]);
+        assert_eq!(matching(&db, range(None, Some(9.5))), ["B"]);
+        assert_eq!(matching(&db, range(Some(9.0), Some(10.0))), ["A", "B"]);
+        // 不是数字的值不满足任何范围
+        assert_eq!(matching(&db, range(None, None)), ["A", "B"]);
+    }
+
+    #[test]
+    fn escapes_wildcards() {
+        assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+        assert_eq!(escape_glob("a*b?[c]"), "a[*]b[?][[]c]");
+    }
+
+    #[test]
+    fn sorts_by_tag_then_name_with_missing_tags_last() {
+        let db = Database::new(":memory:").unwrap();
+        create(&db, "b", &[("priority", "10")]);
+        create(&db, "c", &[("priority", "9")]);
+        create(&db, "d", &[]);
+        create(&db, "A", &[("priority", "9")]);
+        create(&db, "e", &[("priority", "high")]);
+        create(&db, "f", &[("priority", "10kg")]);
+
+        let sorted = |direction: SortDirection| -> Vec<String> {
+            let sort = SortSpec {
+                keys: vec![
+                    SortKey { field: SortField::Tag { key: "priority".to_string() }, direction },
+                    SortKey { field: SortField::Name, direction: SortDirection::Asc },
+                ],
+            };
+            let filter = EventFilter { sort: Some(sort), ..EventFilter::default() };
+            db.filter_events(filter, PageRequest::default())
+                .unwrap()
+                .items
+                .into_iter()
+                .map(|event| event.name)
+                .collect()
+        };
+
+        // 数字值（包括字符串形式的 "10"）按数值比较并排在文本值之前，"10kg" 是文本；
+        // 相同的值按名称排序（不区分大小写），没有该标签的事件在最后
+        assert_eq!(sorted(SortDirection::Asc), ["A", "c", "b", "f", "e", "d"]);
+        assert_eq!(sorted(SortDirection::Desc), ["b", "A", "c", "e", "f", "d"]);
+    }
+}
@@ -0,0 +1,409 @@
+use crate::models::{TodoEvent, ScheduleEntry, CriticalPathReport};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+// 单个事件预估工时的上限（约 10 年），保证关键路径计算不会溢出
+pub const MAX_ESTIMATE_MINUTES: i64 = 60 * 24 * 365 * 10;
+
+#[derive(Debug, Clone)]
+pub struct DependencyNode {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum DependencyError {
+    SelfReference(DependencyNode),
+    UnknownDependency(String),
+    Cycle(Vec<DependencyNode>),
+}
+
+impl fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependencyError::SelfReference(node) => {
+                write!(f, "Event \"{}\" cannot depend on itself", node.name)
+            }
+            DependencyError::UnknownDependency(id) => {
+                write!(f, "Dependency {} does not exist", id)
+            }
+            DependencyError::Cycle(path) => {
+                let names: Vec<&str> = path.iter().map(|node| node.name.as_str()).collect();
+                write!(f, "Dependency cycle detected: {}", names.join(" → "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for DependencyError {}
+
+// 校验事件的依赖列表：不允许自引用、未知ID和环
+// `events` 是数据库中已有的事件，`event` 是即将写入的新版本（可能已存在于 `events` 中）
+pub fn validate_dependencies(event: &TodoEvent, events: &[TodoEvent]) -> Result<(), DependencyError> {
+    let mut names: HashMap<&str, &str> = HashMap::new();
+    let mut edges: HashMap<&str, &[String]> = HashMap::new();
+    for existing in events {
+        if existing.id == event.id {
+            continue;
+        }
+        names.insert(&existing.id, &existing.name);
+        edges.insert(&existing.id, &existing.dependencies);
+    }
+    names.insert(&event.id, &event.name);
+    edges.insert(&event.id, &event.dependencies);
+
+    for dep_id in &event.dependencies {
+        if dep_id == &event.id {
+            return Err(DependencyError::SelfReference(DependencyNode {
+                id: event.id.clone(),
+                name: event.name.clone(),
+            }));
+        }
+        if !names.contains_key(dep_id.as_str()) {
+            return Err(DependencyError::UnknownDependency(dep_id.clone()));
+        }
+    }
+
+    // 已有的图是无环的，所以新出现的环一定经过当前事件
+    let mut path = vec![event.id.as_str()];
+    let mut visited = HashSet::new();
+    if find_path_back(&event.id, &event.id, &edges, &mut visited, &mut path) {
+        let cycle = path
+            .iter()
+            .map(|id| DependencyNode {
+                id: id.to_string(),
+                name: names.get(id).unwrap_or(id).to_string(),
+            })
+            .collect();
+        return Err(DependencyError::Cycle(cycle));
+    }
+
+    Ok(())
+}
+
+fn find_path_back<'a>(
+    current: &str,
+    target: &str,
+    edges: &HashMap<&'a str, &'a [String]>,
+    visited: &mut HashSet<&'a str>,
+    path: &mut Vec<&'a str>,
+) -> bool {
+    let Some(&deps) = edges.get(current) else {
+        return false;
+    };
+    for dep_id in deps {
+        let dep_id = dep_id.as_str();
+        if dep_id == target {
+            path.push(dep_id);
+            return true;
+        }
+        if !visited.insert(dep_id) {
+            continue;
+        }
+        path.push(dep_id);
+        if find_path_back(dep_id, target, edges, visited, path) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+// Kahn 算法：只考虑集合内部的边，前置事件排在依赖它的事件之前。
+// 如果集合中存在环（旧数据），环上的事件按原顺序追加在末尾
+pub fn topological_sort(events: Vec<TodoEvent>) -> Vec<TodoEvent> {
+    let index: HashMap<&str, usize> = events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| (event.id.as_str(), i))
+        .collect();
+
+    let mut in_degree = vec![0usize; events.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); events.len()];
+    for (i, event) in events.iter().enumerate() {
+        for dep_id in &event.dependencies {
+            if let Some(&dep) = index.get(dep_id.as_str()) {
+                in_degree[i] += 1;
+                dependents[dep].push(i);
+            }
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..events.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(events.len());
+    while let Some(i) = queue.pop_front() {
+        order.push(i);
+        for &next in &dependents[i] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                queue.push_back(next);
+            }
+        }
+    }
+    if order.len() < events.len() {
+        let placed: HashSet<usize> = order.iter().copied().collect();
+        order.extend((0..events.len()).filter(|i| !placed.contains(i)));
+    }
+
+    let mut slots: Vec<Option<TodoEvent>> = events.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+// 关键路径法（CPM）：正向计算最早开始/完成时间，反向计算最晚开始/完成时间，
+// 总时差为 0 的事件在关键路径上。只考虑集合内部的依赖边，没有预估工时的事件按 0 分钟计算，
+// 超出范围的预估（旧数据）按上限计算
+pub fn critical_path(events: Vec<TodoEvent>) -> CriticalPathReport {
+    let events = topological_sort(events);
+    let index: HashMap<&str, usize> = events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| (event.id.as_str(), i))
+        .collect();
+
+    let predecessors: Vec<Vec<usize>> = events
+        .iter()
+        .map(|event| {
+            event
+                .dependencies
+                .iter()
+                .filter_map(|dep_id| index.get(dep_id.as_str()).copied())
+                .collect()
+        })
+        .collect();
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); events.len()];
+    for (i, preds) in predecessors.iter().enumerate() {
+        for &pred in preds {
+            successors[pred].push(i);
+        }
+    }
+
+    let durations: Vec<i64> = events
+        .iter()
+        .map(|event| event.estimate_minutes.unwrap_or(0).clamp(0, MAX_ESTIMATE_MINUTES))
+        .collect();
+
+    // 正向计算
+    let mut earliest_start = vec![0i64; events.len()];
+    let mut earliest_finish = vec![0i64; events.len()];
+    for i in 0..events.len() {
+        earliest_start[i] = predecessors[i]
+            .iter()
+            .map(|&pred| earliest_finish[pred])
+            .max()
+            .unwrap_or(0);
+        earliest_finish[i] = earliest_start[i].saturating_add(durations[i]);
+    }
+    let project_duration = earliest_finish.iter().copied().max().unwrap_or(0);
+
+    // 反向计算
+    let mut latest_start = vec![0i64; events.len()];
+    let mut latest_finish = vec![0i64; events.len()];
+    for i in (0..events.len()).rev() {
+        latest_finish[i] = successors[i]
+            .iter()
+            .map(|&succ| latest_start[succ])
+            .min()
+            .unwrap_or(project_duration);
+        latest_start[i] = latest_finish[i].saturating_sub(durations[i]);
+    }
+
+    let critical: Vec<bool> = (0..events.len())
+        .map(|i| latest_start[i] == earliest_start[i])
+        .collect();
+
+    // 从项目开始沿关键事件走到项目结束，得到一条关键路径
+    let mut critical_path = Vec::new();
+    let mut current = (0..events.len()).find(|&i| critical[i] && predecessors[i].is_empty());
+    while let Some(i) = current {
+        critical_path.push(events[i].id.clone());
+        if earliest_finish[i] == project_duration && successors[i].iter().all(|&succ| !critical[succ]) {
+            break;
+        }
+        current = successors[i]
+            .iter()
+            .copied()
+            .find(|&succ| critical[succ] && earliest_start[succ] == earliest_finish[i]);
+    }
+
+    let entries = events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| ScheduleEntry {
+            event_id: event.id.clone(),
+            name: event.name.clone(),
+            duration_minutes: durations[i],
+            has_estimate: event.estimate_minutes.is_some(),
+            earliest_start: earliest_start[i],
+            earliest_finish: earliest_finish[i],
+            latest_start: latest_start[i],
+            latest_finish: latest_finish[i],
+            total_slack: latest_start[i].saturating_sub(earliest_start[i]),
+            critical: critical[i],
+        })
+        .collect();
+
+    CriticalPathReport {
+        entries,
+        critical_path,
+        project_duration_minutes: project_duration,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 事件名称为大写的 ID，便于检查报告中的路径
+    fn event(id: &str, dependencies: &[&str]) -> TodoEvent {
+        let mut event = TodoEvent::new(
+            id.to_uppercase(),
+            String::new(),
+            HashMap::new(),
+            dependencies.iter().map(|dep| dep.to_string()).collect(),
+        );
+        event.id = id.to_string();
+        event
+    }
+
+    #[test]
+    fn rejects_self_reference() {
+        let a = event("a", &["a"]);
+        match validate_dependencies(&a, &[]) {
+            Err(DependencyError::SelfReference(node)) => assert_eq!(node.id, "a"),
+            other => panic!("expected a self reference error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_dependency() {
+        let a = event("a", &[]);
+        let b = event("b", &["a", "missing"]);
+        match validate_dependencies(&b, &[a]) {
+            Err(DependencyError::UnknownDependency(id)) => assert_eq!(id, "missing"),
+            other => panic!("expected an unknown dependency error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_two_node_cycle() {
+        let a = event("a", &[]);
+        let b = event("b", &["a"]);
+        let updated_a = event("a", &["b"]);
+        match validate_dependencies(&updated_a, &[a, b]) {
+            Err(DependencyError::Cycle(path)) => {
+                let ids: Vec<&str> = path.iter().map(|node| node.id.as_str()).collect();
+                assert_eq!(ids, vec!["a", "b", "a"]);
+            }
+            other => panic!("expected a cycle error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_the_path_of_a_three_node_cycle() {
+        // A <- B <- C，再让 A 依赖 C
+        let a = event("a", &[]);
+        let b = event("b", &["a"]);
+        let c = event("c", &["b"]);
+        let updated_a = event("a", &["c"]);
+        let error = validate_dependencies(&updated_a, &[a, b, c]).unwrap_err();
+        match &error {
+            DependencyError::Cycle(path) => {
+                let ids: Vec<&str> = path.iter().map(|node| node.id.as_str()).collect();
+                assert_eq!(ids, vec!["a", "c", "b", "a"]);
+            }
+            other => panic!("expected a cycle error, got {:?}", other),
+        }
+        assert_eq!(error.to_string(), "Dependency cycle detected: A → C → B → A");
+    }
+
+    #[test]
+    fn accepts_a_diamond() {
+        let a = event("a", &[]);
+        let b = event("b", &["a"]);
+        let c = event("c", &["a"]);
+        let d = event("d", &["b", "c"]);
+        assert!(validate_dependencies(&d, &[a, b, c]).is_ok());
+    }
+
+    fn estimated(id: &str, dependencies: &[&str], minutes: i64) -> TodoEvent {
+        let mut event = event(id, dependencies);
+        event.estimate_minutes = Some(minutes);
+        event
+    }
+
+    fn entry<'a>(report: &'a CriticalPathReport, id: &str) -> &'a ScheduleEntry {
+        report.entries.iter().find(|entry| entry.event_id == id).unwrap()
+    }
+
+    #[test]
+    fn schedules_a_diamond() {
+        // A(30) -> B(10), C(20) -> D(5)
+        let report = critical_path(vec![
+            estimated("d", &["b", "c"], 5),
+            estimated("c", &["a"], 20),
+            estimated("b", &["a"], 10),
+            estimated("a", &[], 30),
+        ]);
+
+        assert_eq!(report.project_duration_minutes, 55);
+        assert_eq!(report.critical_path, vec!["a", "c", "d"]);
+        let times = |id| {
+            let entry = entry(&report, id);
+            (entry.earliest_start, entry.earliest_finish, entry.latest_start, entry.latest_finish, entry.total_slack)
+        };
+        assert_eq!(times("a"), (0, 30, 0, 30, 0));
+        assert_eq!(times("b"), (30, 40, 40, 50, 10));
+        assert_eq!(times("c"), (30, 50, 30, 50, 0));
+        assert_eq!(times("d"), (50, 55, 50, 55, 0));
+        assert!(!entry(&report, "b").critical);
+    }
+
+    #[test]
+    fn ties_mark_both_branches_critical() {
+        let report = critical_path(vec![
+            estimated("a", &[], 10),
+            estimated("b", &["a"], 20),
+            estimated("c", &["a"], 20),
+            estimated("d", &["b", "c"], 10),
+        ]);
+
+        assert_eq!(report.project_duration_minutes, 40);
+        assert!(report.entries.iter().all(|entry| entry.critical && entry.total_slack == 0));
+        // 并列时沿拓扑顺序中较早的分支走
+        assert_eq!(report.critical_path, vec!["a", "b", "d"]);
+    }
+
+    #[test]
+    fn schedules_a_disconnected_graph() {
+        // X(10) 与 Y(3) -> Z(4) 互不相连，项目工期由 X 决定
+        let report = critical_path(vec![
+            estimated("y", &[], 3),
+            estimated("z", &["y"], 4),
+            event("w", &[]),
+            estimated("x", &[], 10),
+        ]);
+
+        assert_eq!(report.project_duration_minutes, 10);
+        assert_eq!(report.critical_path, vec!["x"]);
+        assert_eq!(entry(&report, "y").total_slack, 3);
+        assert_eq!(entry(&report, "z").total_slack, 3);
+        assert_eq!(entry(&report, "z").latest_finish, 10);
+
+        let w = entry(&report, "w");
+        assert!(!w.has_estimate);
+        assert_eq!((w.duration_minutes, w.total_slack), (0, 10));
+    }
+
+    #[test]
+    fn clamps_out_of_range_estimates() {
+        let report = critical_path(vec![
+            estimated("a", &[], i64::MAX),
+            estimated("b", &["a"], i64::MAX),
+            estimated("c", &[], -5),
+        ]);
+
+        assert_eq!(report.project_duration_minutes, 2 * MAX_ESTIMATE_MINUTES);
+        assert_eq!(entry(&report, "c").duration_minutes, 0);
+        assert!(report.entries.iter().all(|entry| entry.total_slack >= 0));
+    }
+}
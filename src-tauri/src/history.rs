@@ -0,0 +1,81 @@
+use crate::models::{TodoEvent, FieldChange, HistoryAction, ChangeCause};
+use serde_json::Value;
+
+// 不记录在历史中的字段：id 不会变化，时间戳由每条历史记录自身的时间代替
+const IGNORED_FIELDS: &[&str] = &["id", "created_at", "updated_at"];
+
+// 逐字段比较两个版本的事件；创建时 before 为 None，删除时 after 为 None
+pub fn diff_events(before: Option<&TodoEvent>, after: Option<&TodoEvent>) -> Vec<FieldChange> {
+    let before = to_fields(before);
+    let after = to_fields(after);
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|field| {
+            let old = normalize(before.get(field));
+            let new = normalize(after.get(field));
+            (old != new).then(|| FieldChange { field: field.clone(), old, new })
+        })
+        .collect()
+}
+
+fn to_fields(event: Option<&TodoEvent>) -> serde_json::Map<String, Value> {
+    match event.and_then(|event| serde_json::to_value(event).ok()) {
+        Some(Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    }
+}
+
+// 空的标签和依赖列表视为没有值，创建和删除记录中只保留有意义的字段
+fn normalize(value: Option<&Value>) -> Value {
+    match value {
+        Some(Value::Array(items)) if items.is_empty() => Value::Null,
+        Some(Value::Object(fields)) if fields.is_empty() => Value::Null,
+        Some(Value::String(text)) if text.is_empty() => Value::Null,
+        Some(value) => value.clone(),
+        None => Value::Null,
+    }
+}
+
+pub(crate) fn action_to_str(action: HistoryAction) -> &'static str {
+    match action {
+        HistoryAction::Created => "created",
+        HistoryAction::Updated => "updated",
+        HistoryAction::Deleted => "deleted",
+        HistoryAction::Restored => "restored",
+        HistoryAction::Purged => "purged",
+    }
+}
+
+pub(crate) fn action_from_str(action: &str) -> HistoryAction {
+    match action {
+        "created" => HistoryAction::Created,
+        "deleted" => HistoryAction::Deleted,
+        "restored" => HistoryAction::Restored,
+        "purged" => HistoryAction::Purged,
+        _ => HistoryAction::Updated,
+    }
+}
+
+pub(crate) fn cause_to_str(cause: ChangeCause) -> &'static str {
+    match cause {
+        ChangeCause::User => "user",
+        ChangeCause::Cascade => "cascade",
+        ChangeCause::Import => "import",
+        ChangeCause::Rule => "rule",
+    }
+}
+
+pub(crate) fn cause_from_str(cause: &str) -> ChangeCause {
+    match cause {
+        "cascade" => ChangeCause::Cascade,
+        "import" => ChangeCause::Import,
+        "rule" => ChangeCause::Rule,
+        _ => ChangeCause::User,
+    }
+}
@@ -1,10 +1,21 @@
 mod models;
 mod database;
 mod commands;
+mod filters;
+mod graph;
+mod migrations;
+mod ranking;
+mod query;
+mod history;
+mod error;
+mod executor;
+mod config;
 
-use database::Database;
+use config::ConfigStore;
+use executor::open_startup_database;
 
 use std::sync::Mutex;
+use std::time::Duration;
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -17,14 +28,60 @@ pub fn run() {
             let app_data_dir = app.path().app_data_dir().expect("Failed to get app data directory");
             std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
             
-            // Initialize database
-            let db_path = app_data_dir.join("todo_scheduler.db");
-            println!("Database path: {:?}", db_path);
-            let database = Database::new(&db_path).expect("Failed to initialize database");
+            // Open the database given with --db, otherwise the one used last time,
+            // falling back to the default database in the app data directory
+            let config = ConfigStore::load(app_data_dir.join("config.json"));
+            let default_path = app_data_dir.join("todo_scheduler.db").to_string_lossy().to_string();
+            let requested = config::database_arg(std::env::args().skip(1))
+                .or_else(|| config.active_database().ok().flatten());
+            let startup = open_startup_database(requested.as_deref(), &default_path)
+                .expect("Failed to initialize database");
+            println!("Database path: {}", startup.path);
             
-            // Store database and path in app state
-            app.manage(Mutex::new(database));
-            app.manage(Mutex::new(db_path.to_string_lossy().to_string()));
+            // Remember the opened database in the recent list
+            if let Err(e) = config.record_database(&startup.path) {
+                eprintln!("⚠️ Failed to update recent databases: {}", e);
+            }
+            
+            // Store database, path, config and the fallback warning in app state
+            app.manage(startup.executor);
+            app.manage(Mutex::new(startup.path));
+            app.manage(config);
+            app.manage(Mutex::new(startup.warning));
+
+            // Periodically renew the database lock, release deferred events whose start time has passed,
+            // purge trashed events past the retention period and archive old completed events
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(60));
+                loop {
+                    interval.tick().await;
+                    let db = handle.state::<commands::DbState>();
+                    // Keep our claim on the database, or take it over once the other instance is gone
+                    if let Err(e) = db.heartbeat().await {
+                        eprintln!("🔒 Failed to update database lock: {}", e);
+                    }
+                    let maintenance = db.write(|db| {
+                        Ok((
+                            db.release_deferred_events(),
+                            db.purge_expired_trash(),
+                            db.archive_expired_completed(),
+                        ))
+                    });
+                    let Ok((released, purged, archived)) = maintenance.await else {
+                        continue;
+                    };
+                    if let Err(e) = released {
+                        eprintln!("⏰ Failed to release deferred events: {}", e);
+                    }
+                    if let Err(e) = purged {
+                        eprintln!("🗑️ Failed to purge trash: {}", e);
+                    }
+                    if let Err(e) = archived {
+                        eprintln!("📦 Failed to archive completed events: {}", e);
+                    }
+                }
+            });
             
             Ok(())
         })
@@ -35,15 +92,51 @@ pub fn run() {
             commands::update_event,
             commands::update_event_status,
             commands::delete_event,
+            commands::list_trash,
+            commands::restore_event,
+            commands::purge_trash,
+            commands::get_trash_retention_days,
+            commands::set_trash_retention_days,
+            commands::archive_completed,
+            commands::get_auto_archive_days,
+            commands::set_auto_archive_days,
             commands::filter_events,
+            commands::query_events,
+            commands::search_events,
             commands::get_event_dependencies,
             commands::get_event_dependents,
+            commands::get_event_history,
+            commands::get_critical_path,
+            commands::next_tasks,
+            commands::undo,
+            commands::redo,
+            commands::get_undo_state,
+            commands::create_view,
+            commands::get_views,
+            commands::update_view,
+            commands::delete_view,
+            commands::execute_view,
             commands::get_current_database_path,
+            commands::get_database_status,
+            commands::take_startup_warning,
             commands::get_recent_databases,
+            commands::set_recent_database_pinned,
+            commands::remove_recent_database,
+            commands::prune_recent_databases,
             commands::create_new_database,
             commands::validate_database,
-            commands::switch_database
+            commands::switch_database,
+            commands::show_save_dialog,
+            commands::show_open_dialog
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Release the database lock so another instance can write right away
+            if let tauri::RunEvent::Exit = event {
+                if let Err(e) = app.state::<commands::DbState>().release() {
+                    eprintln!("🔒 Failed to release database lock: {}", e);
+                }
+            }
+        });
 }
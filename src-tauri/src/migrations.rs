@@ -0,0 +1,362 @@
+use crate::error::AppError;
+use rusqlite::{Connection, Result, Transaction};
+
+// 数据库结构版本保存在 PRAGMA user_version 中。
+// 每个迁移步骤把结构从版本 N-1 升级到 N，按顺序在各自的事务中执行。
+// 新增结构变更时只能在末尾追加步骤，已发布的步骤不能修改。
+type Migration = fn(&Transaction) -> Result<()>;
+
+const MIGRATIONS: &[Migration] = &[
+    v1_create_events,
+    v2_event_dependencies,
+    v3_event_dates,
+    v4_event_estimates,
+    v5_full_text_search,
+    v6_event_tags,
+    v7_views,
+    v8_event_history,
+    v9_operations,
+    v10_trash,
+    v11_archive,
+    v12_instance_lock,
+];
+
+pub const LATEST_VERSION: i64 = MIGRATIONS.len() as i64;
+
+// 创建 instance_lock 表的版本
+pub const INSTANCE_LOCK_VERSION: i64 = 12;
+
+pub fn schema_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+// 拒绝由更新版本的应用创建的数据库，避免旧代码误写新结构
+pub fn check_version(conn: &Connection) -> crate::error::Result<i64> {
+    let version = schema_version(conn)?;
+    if version > LATEST_VERSION {
+        return Err(AppError::Schema { version, supported: LATEST_VERSION });
+    }
+    Ok(version)
+}
+
+pub fn run(conn: &Connection) -> crate::error::Result<()> {
+    let current = check_version(conn)?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as i64 + 1;
+        let tx = conn.unchecked_transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+// v1: 最初发布的结构，依赖关系以 JSON 字符串保存在 events 表中
+fn v1_create_events(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS events (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            tags TEXT NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            dependencies TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// v2: 依赖关系迁移到带外键和双向索引的 event_dependencies 表，
+// 指向不存在事件的依赖会被丢弃
+fn v2_event_dependencies(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS event_dependencies (
+            event_id TEXT NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+            depends_on_id TEXT NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+            PRIMARY KEY (event_id, depends_on_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_event_dependencies_depends_on
+            ON event_dependencies(depends_on_id, event_id);",
+    )?;
+
+    let has_legacy_column: bool = tx.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('events') WHERE name = 'dependencies'",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_legacy_column {
+        tx.execute_batch(
+            "INSERT OR IGNORE INTO event_dependencies (event_id, depends_on_id)
+                SELECT e.id, j.value
+                FROM events e, json_each(CASE WHEN json_valid(e.dependencies) THEN e.dependencies ELSE '[]' END) j
+                WHERE j.value IN (SELECT id FROM events) AND j.value != e.id
+                ORDER BY e.rowid, j.key;
+            ALTER TABLE events DROP COLUMN dependencies;",
+        )?;
+    }
+    Ok(())
+}
+
+// v3: 截止时间、延后开始时间和完成时间
+fn v3_event_dates(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE events ADD COLUMN due_at TEXT;
+        ALTER TABLE events ADD COLUMN start_after TEXT;
+        ALTER TABLE events ADD COLUMN completed_at TEXT;
+        UPDATE events SET completed_at = updated_at WHERE status = 'completed';
+        CREATE INDEX IF NOT EXISTS idx_events_due_at ON events(due_at);
+        CREATE INDEX IF NOT EXISTS idx_events_start_after ON events(start_after);",
+    )
+}
+
+// v4: 预估工时（分钟），用于关键路径计算
+fn v4_event_estimates(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("ALTER TABLE events ADD COLUMN estimate_minutes INTEGER;")
+}
+
+// v5: 名称、描述和标签值的 FTS5 全文索引。events 使用 TEXT 主键，隐式的 rowid 在 VACUUM 后
+// 可能被重新编号，所以由 event_search 为每个事件分配稳定的整数键并保存索引的文本，
+// 作为 events_fts 的外部内容表。两级触发器分别同步 event_search 和索引
+fn v5_full_text_search(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS event_search (
+            key INTEGER PRIMARY KEY,
+            event_id TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL,
+            tags TEXT NOT NULL
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS events_fts USING fts5(
+            name, description, tags,
+            content = 'event_search', content_rowid = 'key',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS event_search_insert AFTER INSERT ON event_search BEGIN
+            INSERT INTO events_fts (rowid, name, description, tags)
+            VALUES (NEW.key, NEW.name, NEW.description, NEW.tags);
+        END;
+        CREATE TRIGGER IF NOT EXISTS event_search_update AFTER UPDATE ON event_search BEGIN
+            INSERT INTO events_fts (events_fts, rowid, name, description, tags)
+            VALUES ('delete', OLD.key, OLD.name, OLD.description, OLD.tags);
+            INSERT INTO events_fts (rowid, name, description, tags)
+            VALUES (NEW.key, NEW.name, NEW.description, NEW.tags);
+        END;
+        CREATE TRIGGER IF NOT EXISTS event_search_delete AFTER DELETE ON event_search BEGIN
+            INSERT INTO events_fts (events_fts, rowid, name, description, tags)
+            VALUES ('delete', OLD.key, OLD.name, OLD.description, OLD.tags);
+        END;
+        INSERT INTO event_search (event_id, name, description, tags)
+            SELECT id, name, description, COALESCE((SELECT group_concat(value, ' ') FROM json_each(events.tags)), '')
+            FROM events
+            ORDER BY rowid;
+        CREATE TRIGGER IF NOT EXISTS events_fts_insert AFTER INSERT ON events BEGIN
+            INSERT INTO event_search (event_id, name, description, tags)
+            VALUES (NEW.id, NEW.name, NEW.description, COALESCE((SELECT group_concat(value, ' ') FROM json_each(NEW.tags)), ''));
+        END;
+        CREATE TRIGGER IF NOT EXISTS events_fts_update AFTER UPDATE OF name, description, tags ON events
+        WHEN OLD.name IS NOT NEW.name OR OLD.description IS NOT NEW.description OR OLD.tags IS NOT NEW.tags
+        BEGIN
+            UPDATE event_search
+            SET name = NEW.name,
+                description = NEW.description,
+                tags = COALESCE((SELECT group_concat(value, ' ') FROM json_each(NEW.tags)), '')
+            WHERE event_id = NEW.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS events_fts_delete AFTER DELETE ON events BEGIN
+            DELETE FROM event_search WHERE event_id = OLD.id;
+        END;",
+    )
+}
+
+// v6: 规范化的标签表，用于在 SQL 中按标签过滤和排序，由触发器从 events.tags 同步。
+// num_value 保存可以解析为数字的标签值，用于数值范围查询
+fn v6_event_tags(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS event_tags (
+            event_id TEXT NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            num_value REAL GENERATED ALWAYS AS (
+                CASE WHEN json_valid(value) AND json_type(value) IN ('integer', 'real')
+                    THEN CAST(value AS REAL) END
+            ) STORED,
+            PRIMARY KEY (event_id, key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_event_tags_key_value ON event_tags(key, value);
+        CREATE INDEX IF NOT EXISTS idx_event_tags_key_num_value ON event_tags(key, num_value);
+        INSERT OR IGNORE INTO event_tags (event_id, key, value)
+            SELECT e.id, j.key, j.value FROM events e, json_each(e.tags) j;
+        CREATE TRIGGER IF NOT EXISTS event_tags_insert AFTER INSERT ON events BEGIN
+            INSERT INTO event_tags (event_id, key, value)
+            SELECT NEW.id, key, value FROM json_each(NEW.tags);
+        END;
+        CREATE TRIGGER IF NOT EXISTS event_tags_update AFTER UPDATE OF tags ON events BEGIN
+            DELETE FROM event_tags WHERE event_id = NEW.id;
+            INSERT INTO event_tags (event_id, key, value)
+            SELECT NEW.id, key, value FROM json_each(NEW.tags);
+        END;",
+    )
+}
+
+// v7: 保存的视图，过滤条件以 JSON 保存
+fn v7_views(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS views (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            filter TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+    )
+}
+
+// v8: 只追加的变更历史，不使用外键，事件删除后历史仍然保留
+fn v8_event_history(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS event_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            event_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            cause TEXT NOT NULL,
+            changes TEXT NOT NULL,
+            recorded_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_event_history_event ON event_history(event_id, id);
+        CREATE TRIGGER IF NOT EXISTS event_history_no_update BEFORE UPDATE ON event_history BEGIN
+            SELECT RAISE(ABORT, 'event_history is append-only');
+        END;
+        CREATE TRIGGER IF NOT EXISTS event_history_no_delete BEFORE DELETE ON event_history BEGIN
+            SELECT RAISE(ABORT, 'event_history is append-only');
+        END;",
+    )
+}
+
+// v9: 撤销/重做的操作日志，保存每个操作涉及的事件在操作前后的快照（JSON）
+fn v9_operations(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            label TEXT NOT NULL,
+            before TEXT NOT NULL,
+            after TEXT NOT NULL,
+            undone INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL
+        );",
+    )
+}
+
+// v10: 软删除（回收站）和应用设置。被删除的事件保留依赖边，恢复后依赖关系不变
+fn v10_trash(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE events ADD COLUMN deleted_at TEXT;
+        CREATE INDEX IF NOT EXISTS idx_events_deleted_at ON events(deleted_at);
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )
+}
+
+// v11: 归档已完成的事件，归档的事件不出现在默认列表中，但仍满足依赖
+fn v11_archive(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "ALTER TABLE events ADD COLUMN archived_at TEXT;
+        CREATE INDEX IF NOT EXISTS idx_events_archived_at ON events(archived_at);",
+    )
+}
+
+// v12: 正在写入数据库的应用实例（只有一行），实例定期更新心跳时间，
+// 其他实例在心跳过期前以只读方式打开数据库
+fn v12_instance_lock(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS instance_lock (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            instance_id TEXT NOT NULL,
+            host TEXT NOT NULL,
+            pid INTEGER NOT NULL,
+            heartbeat_at TEXT NOT NULL
+        );",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 版本 0 的数据库：依赖关系以 JSON 保存，包含不存在的事件和指向自身的依赖
+    fn legacy_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE events (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                status TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                dependencies TEXT NOT NULL
+            );
+            INSERT INTO events VALUES
+                ('a', 'A', '', '{}', 'completed', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '[]'),
+                ('b', 'B', '', '{}', 'pending', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '[\"a\", \"missing\"]'),
+                ('c', 'C', '', '{}', 'blocked', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '[\"b\", \"a\", \"c\"]'),
+                ('d', 'D', '', '{}', 'pending', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', 'not json');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn upgrades_version_0_database() {
+        let conn = legacy_database();
+        run(&conn).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+
+        let mut stmt = conn
+            .prepare("SELECT event_id, depends_on_id FROM event_dependencies ORDER BY event_id, depends_on_id")
+            .unwrap();
+        let edges: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected = [("b", "a"), ("c", "a"), ("c", "b")];
+        assert_eq!(
+            edges,
+            expected.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect::<Vec<_>>()
+        );
+
+        let has_legacy_column: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('events') WHERE name = 'dependencies'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!has_legacy_column);
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let conn = legacy_database();
+        run(&conn).unwrap();
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1).unwrap();
+
+        match check_version(&conn) {
+            Err(AppError::Schema { version, supported }) => {
+                assert_eq!(version, LATEST_VERSION + 1);
+                assert_eq!(supported, LATEST_VERSION);
+            }
+            other => panic!("expected a schema error, got {:?}", other),
+        }
+        assert!(run(&conn).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -13,6 +13,12 @@ pub struct TodoEvent {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub dependencies: Vec<String>, // IDs of dependent events
+    pub due_at: Option<DateTime<Utc>>,
+    pub start_after: Option<DateTime<Utc>>, // Deferred until this time
+    pub completed_at: Option<DateTime<Utc>>,
+    pub estimate_minutes: Option<i64>, // Estimated duration
+    pub deleted_at: Option<DateTime<Utc>>, // Moved to trash at this time
+    pub archived_at: Option<DateTime<Utc>>, // Hidden from the default listing since this time
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -29,12 +35,40 @@ impl Default for EventStatus {
     }
 }
 
+// 删除一个仍被其他事件依赖的事件时的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum DeletePolicy {
+    // 存在依赖它的事件时拒绝删除
+    #[default]
+    Refuse,
+    // 移除依赖边并重新计算依赖它的事件的状态
+    Detach,
+    // 连同所有（传递）依赖它的事件一起删除
+    Cascade,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteEventResult {
+    pub deleted: Vec<String>,
+    pub updated: Vec<TodoEvent>,
+}
+
+// 编辑后的事件，以及因状态级联而变化的其他事件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateEventResult {
+    pub event: TodoEvent,
+    pub updated: Vec<TodoEvent>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateEventRequest {
     pub name: String,
     pub description: String,
     pub tags: HashMap<String, String>,
     pub dependencies: Vec<String>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub start_after: Option<DateTime<Utc>>,
+    pub estimate_minutes: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -45,13 +79,233 @@ pub struct UpdateEventRequest {
     pub tags: Option<HashMap<String, String>>,
     pub status: Option<EventStatus>,
     pub dependencies: Option<Vec<String>>,
+    // 外层 None 表示不修改，Some(None) 表示清除
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub start_after: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub estimate_minutes: Option<Option<i64>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EventFilter {
     pub status: Option<EventStatus>,
     pub tags: Option<HashMap<String, String>>,
     pub search: Option<String>,
+    pub tag_predicates: Option<Vec<TagPredicate>>,
+    pub due_before: Option<DateTime<Utc>>,
+    pub due_after: Option<DateTime<Utc>>,
+    // true: 只返回 start_after 在未来的事件；false: 排除这些事件
+    pub deferred: Option<bool>,
+    // 默认不包含已归档的事件
+    pub include_archived: Option<bool>,
+    // 查询语言文本，与其他条件之间为 AND，语法见 query.rs
+    pub query: Option<String>,
+    pub sort: Option<SortSpec>,
+}
+
+// 分页参数；没有 limit 时返回全部结果，cursor 是上一页返回的 next_cursor
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PageRequest {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub total: usize, // Number of matches across all pages
+}
+
+// 多级排序：依次比较每个排序键，最后按 id 保证排序稳定
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SortSpec {
+    pub keys: Vec<SortKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SortKey {
+    pub field: SortField,
+    pub direction: SortDirection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SortField {
+    // 数字标签值按数值比较并排在文本值之前，文本不区分大小写；缺少该标签的事件排在最后
+    Tag { key: String },
+    Name,
+    Created,
+    Updated,
+    // 没有截止时间的事件排在最后
+    Due,
+    // 待办、进行中、阻塞、已完成
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+// 标签过滤条件，在 SQL 中通过 event_tags 表求值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TagPredicate {
+    Exists { key: String },
+    Missing { key: String },
+    In { key: String, values: Vec<String> },
+    Prefix { key: String, prefix: String },
+    // 只匹配值为数字的标签，上下界都包含
+    Range { key: String, min: Option<f64>, max: Option<f64> },
+}
+
+// 关键路径计算结果中的单个事件，时间均为相对项目开始的分钟数
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub event_id: String,
+    pub name: String,
+    pub duration_minutes: i64,
+    pub has_estimate: bool,
+    pub earliest_start: i64,
+    pub earliest_finish: i64,
+    pub latest_start: i64,
+    pub latest_finish: i64,
+    pub total_slack: i64,
+    pub critical: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CriticalPathReport {
+    pub entries: Vec<ScheduleEntry>,
+    pub critical_path: Vec<String>, // Event IDs from first to last task
+    pub project_duration_minutes: i64,
+}
+
+// 全文搜索结果，高亮部分用 <mark></mark> 包裹
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub event: TodoEvent,
+    pub name_highlight: String,
+    pub snippet: String,
+    pub score: f64, // Negated BM25, higher is more relevant
+}
+
+// 保存在数据库中的命名视图，过滤条件（包括排序）随 .db 文件一起保存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedView {
+    pub id: String,
+    pub name: String,
+    pub filter: EventFilter,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateViewRequest {
+    pub name: String,
+    pub filter: EventFilter,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateViewRequest {
+    pub id: String,
+    pub name: Option<String>,
+    pub filter: Option<EventFilter>,
+}
+
+// 变更的来源
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ChangeCause {
+    // 用户通过界面直接修改
+    User,
+    // 前置事件变化引起的级联更新
+    Cascade,
+    // 从外部数据导入
+    Import,
+    // 自动规则，例如延后时间到期
+    Rule,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum HistoryAction {
+    Created,
+    Updated,
+    // 移入回收站
+    Deleted,
+    // 从回收站恢复
+    Restored,
+    // 从回收站永久删除
+    Purged,
+}
+
+// 单个字段的变化，值为字段的 JSON 表示，不存在时为 null
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub event_id: String,
+    pub action: HistoryAction,
+    pub cause: ChangeCause,
+    pub changes: Vec<FieldChange>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+// 操作日志中一个事件在某个时刻的完整状态，event 为 None 表示该事件不存在
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSnapshot {
+    pub id: String,
+    pub event: Option<TodoEvent>,
+}
+
+// 撤销/重做的结果：恢复后存在的事件和被移除的事件 ID
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoResult {
+    pub label: String,
+    pub restored: Vec<TodoEvent>,
+    pub deleted: Vec<String>,
+}
+
+// 当前可以撤销和重做的操作名称，用于界面显示
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoState {
+    pub undo: Option<String>,
+    pub redo: Option<String>,
+}
+
+// 持有数据库写锁的应用实例
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceLock {
+    pub instance_id: String,
+    pub host: String,
+    pub pid: u32,
+    pub heartbeat_at: DateTime<Utc>,
+}
+
+// 当前数据库的打开状态；其他实例正在写入时为只读，locked_by 为该实例
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStatus {
+    pub read_only: bool,
+    pub locked_by: Option<InstanceLock>,
+}
+
+// 区分字段缺失（不修改）和显式的 null（清除）
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 impl TodoEvent {
@@ -66,7 +320,33 @@ impl TodoEvent {
             created_at: now,
             updated_at: now,
             dependencies,
+            due_at: None,
+            start_after: None,
+            completed_at: None,
+            estimate_minutes: None,
+            deleted_at: None,
+            archived_at: None,
+        }
+    }
+
+    // 修改状态并维护完成时间
+    pub fn set_status(&mut self, status: EventStatus) {
+        if status == EventStatus::Completed {
+            if self.status != EventStatus::Completed || self.completed_at.is_none() {
+                self.completed_at = Some(Utc::now());
+            }
+        } else {
+            // 重新打开的事件回到日常列表中
+            self.completed_at = None;
+            self.archived_at = None;
         }
+        self.status = status;
+        self.updated_at = Utc::now();
+    }
+
+    // start_after 在未来的事件暂不可开始
+    pub fn is_deferred(&self) -> bool {
+        self.start_after.is_some_and(|start_after| start_after > Utc::now())
     }
 
     pub fn update(&mut self, request: UpdateEventRequest) {
@@ -80,11 +360,20 @@ impl TodoEvent {
             self.tags = tags;
         }
         if let Some(status) = request.status {
-            self.status = status;
+            self.set_status(status);
         }
         if let Some(dependencies) = request.dependencies {
             self.dependencies = dependencies;
         }
+        if let Some(due_at) = request.due_at {
+            self.due_at = due_at;
+        }
+        if let Some(start_after) = request.start_after {
+            self.start_after = start_after;
+        }
+        if let Some(estimate_minutes) = request.estimate_minutes {
+            self.estimate_minutes = estimate_minutes;
+        }
         self.updated_at = Utc::now();
     }
 }
\ No newline at end of file
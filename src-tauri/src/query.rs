@@ -0,0 +1,633 @@
+use crate::database::status_to_str;
+use crate::filters::{self, SqlCondition};
+use crate::models::{EventStatus, TagPredicate};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::types::Value;
+use std::fmt;
+
+// 文本查询语言，例如：
+//   status:ready tag:area=work due<7d -tag:someday "api" depends-on:<id>
+// 多个条件之间默认为 AND，支持 OR、NOT / - 取反和括号分组。
+// 不带字段的词和引号内的短语匹配名称或描述。
+//
+// 字段：
+//   status:<pending|in_progress|completed|blocked|ready|deferred>
+//   tag:<key>  tag:<key>=<value>  tag:<key>=<prefix>*  tag:<key><op><number>
+//   due<op><date>  due=<date>  due:none  due:any
+//     <date> 可以是 YYYY-MM-DD、RFC 3339 时间、now、today 或相对时间 7d / 12h / 2w / 30m（可带负号）
+//   depends-on:<id>   直接依赖给定事件的事件
+//   required-by:<id>  给定事件直接依赖的事件
+
+// 位置按字符计数，从 0 开始
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid query at position {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+fn error<T>(message: impl Into<String>, position: usize) -> Result<T, QueryError> {
+    Err(QueryError { message: message.into(), position })
+}
+
+#[derive(Debug, Clone)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn sql(&self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatusTerm {
+    Is(EventStatus),
+    Ready,
+    Deferred,
+}
+
+#[derive(Debug, Clone)]
+pub enum DueTerm {
+    Compare(Comparison, DateTime<Utc>),
+    On(DateTime<Utc>),
+    None,
+    Any,
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    Text(String),
+    Status(StatusTerm),
+    Tag(TagPredicate),
+    TagCompare { key: String, comparison: Comparison, value: f64 },
+    Due(DueTerm),
+    DependsOn(String),
+    RequiredBy(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum TokenKind {
+    Word { text: String, quoted: bool },
+    LParen,
+    RParen,
+    Minus,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+    // 单词中每个字符在原始输入中的位置，用于报告字段值的错误位置
+    offsets: Vec<usize>,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let simple = match c {
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            // 只有紧跟在条件前面的 - 才表示取反，-5 这样的负数是普通的词
+            '-' if chars
+                .get(i + 1)
+                .is_some_and(|next| !next.is_whitespace() && *next != ')' && !next.is_ascii_digit()) =>
+            {
+                Some(TokenKind::Minus)
+            }
+            _ => None,
+        };
+        if let Some(kind) = simple {
+            tokens.push(Token { kind, position: i, offsets: Vec::new() });
+            i += 1;
+            continue;
+        }
+
+        // 单词中可以包含引号括起来的部分，例如 tag:area="deep work"
+        let start = i;
+        let mut text = String::new();
+        let mut offsets = Vec::new();
+        let quoted = c == '"';
+        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+            if chars[i] == '"' {
+                let quote = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    text.push(chars[i]);
+                    offsets.push(i);
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return error("Unterminated quote", quote);
+                }
+                i += 1;
+                if quoted {
+                    break;
+                }
+            } else {
+                text.push(chars[i]);
+                offsets.push(i);
+                i += 1;
+            }
+        }
+        tokens.push(Token { kind: TokenKind::Word { text, quoted }, position: start, offsets });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    end: usize,
+    now: DateTime<Utc>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(
+            self.peek().map(|token| &token.kind),
+            Some(TokenKind::Word { text, quoted: false }) if text == keyword
+        )
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map(|token| token.position).unwrap_or(self.end)
+    }
+
+    // or := and ("OR" and)*
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while self.is_keyword("OR") {
+            self.index += 1;
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Expr::Or(branches) })
+    }
+
+    // and := unary (["AND"] unary)*
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut terms = vec![self.parse_unary()?];
+        loop {
+            if self.is_keyword("AND") {
+                self.index += 1;
+            } else if self.peek().is_none()
+                || self.is_keyword("OR")
+                || matches!(self.peek().map(|token| &token.kind), Some(TokenKind::RParen))
+            {
+                break;
+            }
+            terms.push(self.parse_unary()?);
+        }
+        Ok(if terms.len() == 1 { terms.pop().unwrap() } else { Expr::And(terms) })
+    }
+
+    // unary := ("-" | "NOT") unary | "(" or ")" | term
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        let position = self.position();
+        let Some(token) = self.peek().cloned() else {
+            return error("Expected a condition", position);
+        };
+        match token.kind {
+            TokenKind::Minus => {
+                self.index += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::Word { ref text, quoted: false } if text == "NOT" => {
+                self.index += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::Word { ref text, quoted: false } if text == "AND" || text == "OR" => {
+                error(format!("Expected a condition before {}", text), position)
+            }
+            TokenKind::LParen => {
+                self.index += 1;
+                let expr = self.parse_or()?;
+                match self.peek().map(|token| &token.kind) {
+                    Some(TokenKind::RParen) => {
+                        self.index += 1;
+                        Ok(expr)
+                    }
+                    _ => error("Expected closing parenthesis", self.position()),
+                }
+            }
+            TokenKind::RParen => error("Unexpected closing parenthesis", position),
+            TokenKind::Word { text, quoted } => {
+                self.index += 1;
+                if quoted {
+                    return Ok(Expr::Term(Term::Text(text)));
+                }
+                Ok(Expr::Term(self.parse_term(&text, &token.offsets)?))
+            }
+        }
+    }
+
+    fn parse_term(&self, text: &str, offsets: &[usize]) -> Result<Term, QueryError> {
+        let chars: Vec<char> = text.chars().collect();
+        let at = |index: usize| offsets.get(index).copied().unwrap_or(self.end);
+
+        let field_len = chars
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic() || **c == '-' || **c == '_')
+            .count();
+        let Some((operator, operator_len)) = read_operator(&chars[field_len..], true) else {
+            return Ok(Term::Text(text.to_string()));
+        };
+        if field_len == 0 {
+            return Ok(Term::Text(text.to_string()));
+        }
+        let field: String = chars[..field_len].iter().collect::<String>().to_lowercase();
+        let value_start = field_len + operator_len;
+        let value: String = chars[value_start..].iter().collect();
+        let value_position = at(value_start);
+
+        let require_colon = |field: &str| -> Result<(), QueryError> {
+            if operator == ":" {
+                Ok(())
+            } else {
+                error(format!("Field \"{}\" only supports \":\"", field), at(field_len))
+            }
+        };
+        let require_value = || -> Result<(), QueryError> {
+            if value.is_empty() {
+                error(format!("Missing value for \"{}\"", field), value_position)
+            } else {
+                Ok(())
+            }
+        };
+
+        match field.as_str() {
+            "status" => {
+                require_colon("status")?;
+                require_value()?;
+                let status = match value.to_lowercase().replace('-', "_").as_str() {
+                    "pending" => StatusTerm::Is(EventStatus::Pending),
+                    "in_progress" => StatusTerm::Is(EventStatus::InProgress),
+                    "completed" | "done" => StatusTerm::Is(EventStatus::Completed),
+                    "blocked" => StatusTerm::Is(EventStatus::Blocked),
+                    "ready" => StatusTerm::Ready,
+                    "deferred" => StatusTerm::Deferred,
+                    _ => return error(format!("Unknown status \"{}\"", value), value_position),
+                };
+                Ok(Term::Status(status))
+            }
+            "tag" => {
+                require_colon("tag")?;
+                require_value()?;
+                self.parse_tag(&chars[value_start..], &offsets[value_start.min(offsets.len())..])
+            }
+            "due" => {
+                require_value()?;
+                if operator == ":" {
+                    return match value.to_lowercase().as_str() {
+                        "none" => Ok(Term::Due(DueTerm::None)),
+                        "any" => Ok(Term::Due(DueTerm::Any)),
+                        _ => error("Expected \"none\", \"any\" or a comparison such as due<7d", value_position),
+                    };
+                }
+                let date = self.parse_date(&value, value_position)?;
+                match comparison(operator) {
+                    Some(comparison) => Ok(Term::Due(DueTerm::Compare(comparison, date))),
+                    // due=<date> 表示当天到期
+                    None => Ok(Term::Due(DueTerm::On(start_of_day(date.date_naive())))),
+                }
+            }
+            "depends-on" | "depends_on" => {
+                require_colon(&field)?;
+                require_value()?;
+                Ok(Term::DependsOn(value))
+            }
+            "required-by" | "required_by" => {
+                require_colon(&field)?;
+                require_value()?;
+                Ok(Term::RequiredBy(value))
+            }
+            _ => error(
+                format!("Unknown field \"{}\" (quote the text to search for it literally)", field),
+                at(0),
+            ),
+        }
+    }
+
+    fn parse_tag(&self, chars: &[char], offsets: &[usize]) -> Result<Term, QueryError> {
+        let at = |index: usize| offsets.get(index).copied().unwrap_or(self.end);
+        let key_len = chars
+            .iter()
+            .take_while(|c| !matches!(c, '=' | '<' | '>'))
+            .count();
+        let key: String = chars[..key_len].iter().collect();
+        if key.is_empty() {
+            return error("Missing tag key", at(0));
+        }
+        let Some((operator, operator_len)) = read_operator(&chars[key_len..], false) else {
+            return Ok(Term::Tag(TagPredicate::Exists { key }));
+        };
+        let value: String = chars[key_len + operator_len..].iter().collect();
+        let value_position = at(key_len + operator_len);
+
+        if operator == "=" {
+            if let Some(prefix) = value.strip_suffix('*') {
+                return Ok(Term::Tag(TagPredicate::Prefix { key, prefix: prefix.to_string() }));
+            }
+            return Ok(Term::Tag(TagPredicate::In { key, values: vec![value] }));
+        }
+        match (value.parse::<f64>(), comparison(operator)) {
+            (Ok(number), Some(comparison)) if number.is_finite() => Ok(Term::TagCompare {
+                key,
+                comparison,
+                value: number,
+            }),
+            _ => error(format!("Expected a number after \"{}\"", operator), value_position),
+        }
+    }
+
+    fn parse_date(&self, value: &str, position: usize) -> Result<DateTime<Utc>, QueryError> {
+        let out_of_range = || error(format!("Date \"{}\" is out of range", value), position);
+        match value.to_lowercase().as_str() {
+            "now" => return Ok(self.now),
+            "today" => return Ok(start_of_day(self.now.date_naive())),
+            "tomorrow" => {
+                return start_of_day(self.now.date_naive())
+                    .checked_add_signed(Duration::days(1))
+                    .map_or_else(out_of_range, Ok)
+            }
+            _ => {}
+        }
+        if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+            return Ok(date.with_timezone(&Utc));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(start_of_day(date));
+        }
+
+        // 相对时间：数字加单位 m / h / d / w
+        let (number, unit) = value.split_at(value.len() - value.chars().last().map_or(0, |c| c.len_utf8()));
+        let amount = number.parse::<i64>().ok();
+        let duration = match (amount, unit) {
+            (Some(n), "m") => Duration::try_minutes(n),
+            (Some(n), "h") => Duration::try_hours(n),
+            (Some(n), "d") => Duration::try_days(n),
+            (Some(n), "w") => Duration::try_weeks(n),
+            _ => None,
+        };
+        match duration {
+            Some(duration) => self.now.checked_add_signed(duration).map_or_else(out_of_range, Ok),
+            None => error(
+                format!("Invalid date \"{}\" (use YYYY-MM-DD, now, today or a relative time like 7d)", value),
+                position,
+            ),
+        }
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+// 读取字段名后面的运算符，返回运算符及其长度
+fn read_operator(chars: &[char], allow_colon: bool) -> Option<(&'static str, usize)> {
+    match (chars.first(), chars.get(1)) {
+        (Some(':'), _) if allow_colon => Some((":", 1)),
+        (Some('<'), Some('=')) => Some(("<=", 2)),
+        (Some('>'), Some('=')) => Some((">=", 2)),
+        (Some('<'), _) => Some(("<", 1)),
+        (Some('>'), _) => Some((">", 1)),
+        (Some('='), _) => Some(("=", 1)),
+        _ => None,
+    }
+}
+
+fn comparison(operator: &str) -> Option<Comparison> {
+    match operator {
+        "<" => Some(Comparison::Lt),
+        "<=" => Some(Comparison::Le),
+        ">" => Some(Comparison::Gt),
+        ">=" => Some(Comparison::Ge),
+        _ => None,
+    }
+}
+
+// 解析查询文本，空查询返回 None（匹配所有事件）
+pub fn parse(input: &str, now: DateTime<Utc>) -> Result<Option<Expr>, QueryError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = Parser { tokens, index: 0, end: input.chars().count(), now };
+    let expr = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return error("Unexpected closing parenthesis", token.position);
+    }
+    Ok(Some(expr))
+}
+
+// 把查询编译为参数化的 WHERE 条件
+pub fn compile(expr: &Expr, now: DateTime<Utc>) -> SqlCondition {
+    match expr {
+        Expr::Term(term) => compile_term(term, now),
+        Expr::Not(inner) => compile(inner, now).negate(),
+        Expr::And(terms) => SqlCondition::and(terms.iter().map(|term| compile(term, now)).collect()),
+        Expr::Or(branches) => SqlCondition::or(branches.iter().map(|branch| compile(branch, now)).collect()),
+    }
+}
+
+fn compile_term(term: &Term, now: DateTime<Utc>) -> SqlCondition {
+    let now = Value::Text(now.to_rfc3339());
+    match term {
+        Term::Text(text) => filters::text_contains_condition(text),
+        Term::Status(StatusTerm::Is(status)) => {
+            SqlCondition::new("e.status = ?", vec![Value::Text(status_to_str(*status).to_string())])
+        }
+        Term::Status(StatusTerm::Ready) => SqlCondition::new(
+            "e.status = 'pending' AND (e.start_after IS NULL OR e.start_after <= ?)",
+            vec![now],
+        ),
+        Term::Status(StatusTerm::Deferred) => SqlCondition::new("e.start_after > ?", vec![now]),
+        Term::Tag(predicate) => filters::tag_condition(predicate),
+        Term::TagCompare { key, comparison, value } => SqlCondition::new(
+            format!(
+                "EXISTS (SELECT 1 FROM event_tags t WHERE t.event_id = e.id AND t.key = ? AND t.num_value {} ?)",
+                comparison.sql()
+            ),
+            vec![Value::Text(key.clone()), Value::Real(*value)],
+        ),
+        Term::Due(DueTerm::None) => SqlCondition::new("e.due_at IS NULL", Vec::new()),
+        Term::Due(DueTerm::Any) => SqlCondition::new("e.due_at IS NOT NULL", Vec::new()),
+        Term::Due(DueTerm::Compare(comparison, date)) => SqlCondition::new(
+            format!("e.due_at {} ?", comparison.sql()),
+            vec![Value::Text(date.to_rfc3339())],
+        ),
+        Term::Due(DueTerm::On(day)) => SqlCondition::new(
+            "e.due_at >= ? AND e.due_at < ?",
+            vec![
+                Value::Text(day.to_rfc3339()),
+                // 最后一个可表示的日期没有下一天，使用最大时间作为上界
+                Value::Text(day.checked_add_signed(Duration::days(1)).unwrap_or(DateTime::<Utc>::MAX_UTC).to_rfc3339()),
+            ],
+        ),
+        Term::DependsOn(id) => SqlCondition::new(
+            "EXISTS (SELECT 1 FROM event_dependencies d WHERE d.event_id = e.id AND d.depends_on_id = ?)",
+            vec![Value::Text(id.clone())],
+        ),
+        Term::RequiredBy(id) => SqlCondition::new(
+            "EXISTS (SELECT 1 FROM event_dependencies d WHERE d.depends_on_id = e.id AND d.event_id = ?)",
+            vec![Value::Text(id.clone())],
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_dates_are_query_errors() {
+        let now = Utc::now();
+        let error = parse("due<99999999w", now).unwrap_err();
+        assert_eq!(error.position, 4);
+        assert!(parse("due>9999999999d", now).is_err());
+        assert!(parse("due<7d", now).unwrap().is_some());
+    }
+
+    #[test]
+    fn due_on_the_last_representable_day_compiles() {
+        let last_day = DateTime::<Utc>::MAX_UTC.date_naive().format("%Y-%m-%d").to_string();
+        let expr = parse(&format!("due={}", last_day), Utc::now()).unwrap().unwrap();
+        compile(&expr, Utc::now());
+    }
+
+    // 把表达式写成紧凑的前缀形式，便于比较结构
+    fn render(expr: &Expr) -> String {
+        let join = |exprs: &[Expr]| exprs.iter().map(render).collect::<Vec<_>>().join(" ");
+        match expr {
+            Expr::Term(Term::Text(text)) => text.clone(),
+            Expr::Term(term) => format!("{:?}", term),
+            Expr::Not(inner) => format!("(not {})", render(inner)),
+            Expr::And(terms) => format!("(and {})", join(terms)),
+            Expr::Or(branches) => format!("(or {})", join(branches)),
+        }
+    }
+
+    fn parsed(input: &str) -> String {
+        render(&parse(input, Utc::now()).unwrap().unwrap())
+    }
+
+    fn error_at(input: &str) -> usize {
+        parse(input, Utc::now()).unwrap_err().position
+    }
+
+    #[test]
+    fn parses_the_example_query() {
+        let now = Utc::now();
+        let expr = parse(r#"status:ready tag:area=work due<7d -tag:someday "api" depends-on:abc"#, now)
+            .unwrap()
+            .unwrap();
+        let Expr::And(terms) = &expr else { panic!("expected AND, got {:?}", expr) };
+        assert_eq!(terms.len(), 6);
+        assert!(matches!(terms[0], Expr::Term(Term::Status(StatusTerm::Ready))));
+        assert!(matches!(
+            &terms[1],
+            Expr::Term(Term::Tag(TagPredicate::In { key, values })) if key == "area" && values == &["work"]
+        ));
+        assert!(matches!(
+            &terms[2],
+            Expr::Term(Term::Due(DueTerm::Compare(Comparison::Lt, date))) if *date == now + Duration::days(7)
+        ));
+        assert!(matches!(
+            &terms[3],
+            Expr::Not(inner) if matches!(&**inner, Expr::Term(Term::Tag(TagPredicate::Exists { key })) if key == "someday")
+        ));
+        assert!(matches!(&terms[4], Expr::Term(Term::Text(text)) if text == "api"));
+        assert!(matches!(&terms[5], Expr::Term(Term::DependsOn(id)) if id == "abc"));
+
+        let condition = compile(&expr, now);
+        assert_eq!(condition.sql.matches('?').count(), condition.params.len());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parsed("a b OR c"), "(or (and a b) c)");
+        assert_eq!(parsed("a AND b OR c AND d"), "(or (and a b) (and c d))");
+        assert_eq!(parsed("a (b OR c)"), "(and a (or b c))");
+        assert_eq!(parsed("NOT a OR b"), "(or (not a) b)");
+        assert_eq!(parsed("-(a OR b) c"), "(and (not (or a b)) c)");
+        assert_eq!(parsed("NOT NOT a"), "(not (not a))");
+        assert_eq!(parsed("((a))"), "a");
+    }
+
+    #[test]
+    fn quoted_text_is_a_single_literal_term() {
+        assert_eq!(parsed(r#""deep work" OR x"#), "(or deep work x)");
+        // 引号内的关键字、字段和括号都是普通文本
+        assert_eq!(parsed(r#""a OR b""#), "a OR b");
+        assert_eq!(parsed(r#""status:ready""#), "status:ready");
+        assert_eq!(parsed(r#""(x)""#), "(x)");
+        assert!(matches!(
+            parse(r#"tag:area="deep work""#, Utc::now()).unwrap(),
+            Some(Expr::Term(Term::Tag(TagPredicate::In { values, .. }))) if values == ["deep work"]
+        ));
+    }
+
+    #[test]
+    fn leading_minus_before_a_digit_is_a_number() {
+        assert_eq!(parsed("-5"), "-5");
+        assert_eq!(parsed("a -5"), "(and a -5)");
+        assert_eq!(parsed("-x"), "(not x)");
+        assert_eq!(parsed("a - b"), "(and a - b)");
+        assert!(matches!(
+            parse("tag:size>-5", Utc::now()).unwrap(),
+            Some(Expr::Term(Term::TagCompare { value, .. })) if value == -5.0
+        ));
+    }
+
+    #[test]
+    fn errors_report_the_position_of_the_problem() {
+        assert_eq!(error_at(r#"a "open"#), 2);
+        assert_eq!(error_at("(a b"), 4);
+        assert_eq!(error_at("a b)"), 3);
+        assert_eq!(error_at("a OR"), 4);
+        assert_eq!(error_at("OR a"), 0);
+        assert_eq!(error_at("a ()"), 3);
+        assert_eq!(error_at("x status:nope"), 9);
+        assert_eq!(error_at("x colour:red"), 2);
+        assert_eq!(error_at("tag:size>big"), 9);
+        assert_eq!(error_at("tag:=x"), 4);
+        assert_eq!(error_at("status=ready"), 6);
+        assert_eq!(error_at("due<soon"), 4);
+        assert!(parse("  ", Utc::now()).unwrap().is_none());
+    }
+}
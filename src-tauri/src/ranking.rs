@@ -0,0 +1,299 @@
+use crate::models::{TodoEvent, EventStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// 排序权重，每个因子先归一化到 [0, 1] 再乘以权重
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingWeights {
+    pub urgency: f64,
+    pub priority: f64,
+    pub unblocks: f64,
+    pub effort: f64,
+    pub age: f64,
+    pub priority_tag: String, // Tag key that holds the priority
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        Self {
+            urgency: 3.0,
+            priority: 2.0,
+            unblocks: 1.5,
+            effort: 0.5,
+            age: 0.5,
+            priority_tag: "priority".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreComponent {
+    pub factor: f64,
+    pub weight: f64,
+    pub contribution: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    pub urgency: ScoreComponent,
+    pub priority: ScoreComponent,
+    pub unblocks: ScoreComponent,
+    pub effort: ScoreComponent,
+    pub age: ScoreComponent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedTask {
+    pub event: TodoEvent,
+    pub score: f64,
+    pub breakdown: ScoreBreakdown,
+}
+
+// 对可以立即开始的事件（待办且未延后）打分并按分数从高到低排序
+pub fn rank_ready_events(events: &[TodoEvent], weights: &RankingWeights, now: DateTime<Utc>) -> Vec<RankedTask> {
+    let mut dependents: HashMap<&str, Vec<&TodoEvent>> = HashMap::new();
+    for event in events {
+        for dep_id in &event.dependencies {
+            dependents.entry(dep_id.as_str()).or_default().push(event);
+        }
+    }
+
+    let mut ranked: Vec<RankedTask> = events
+        .iter()
+        .filter(|event| event.status == EventStatus::Pending && event.start_after.is_none_or(|start| start <= now))
+        .map(|event| {
+            let unblocked = count_open_dependents(&event.id, &dependents);
+            let breakdown = ScoreBreakdown {
+                urgency: component(urgency_factor(event, now), weights.urgency),
+                priority: component(priority_factor(event, &weights.priority_tag), weights.priority),
+                unblocks: component(unblocks_factor(unblocked), weights.unblocks),
+                effort: component(effort_factor(event), weights.effort),
+                age: component(age_factor(event, now), weights.age),
+            };
+            let score = breakdown.urgency.contribution
+                + breakdown.priority.contribution
+                + breakdown.unblocks.contribution
+                + breakdown.effort.contribution
+                + breakdown.age.contribution;
+            RankedTask { event: event.clone(), score, breakdown }
+        })
+        .collect();
+
+    // 分数相同时先创建的排在前面，保证排序稳定
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.event.created_at.cmp(&b.event.created_at))
+            .then_with(|| a.event.id.cmp(&b.event.id))
+    });
+    ranked
+}
+
+fn component((factor, reason): (f64, String), weight: f64) -> ScoreComponent {
+    ScoreComponent {
+        factor,
+        weight,
+        contribution: factor * weight,
+        reason,
+    }
+}
+
+// 统计完成该事件后（传递地）被推进的未完成事件数量
+fn count_open_dependents(id: &str, dependents: &HashMap<&str, Vec<&TodoEvent>>) -> usize {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut stack = vec![id];
+    while let Some(current) = stack.pop() {
+        for dependent in dependents.get(current).into_iter().flatten() {
+            if dependent.status != EventStatus::Completed && seen.insert(dependent.id.as_str()) {
+                stack.push(dependent.id.as_str());
+            }
+        }
+    }
+    seen.len()
+}
+
+// 已过期为 1，越接近截止时间越接近 1，没有截止时间为 0
+fn urgency_factor(event: &TodoEvent, now: DateTime<Utc>) -> (f64, String) {
+    match event.due_at {
+        None => (0.0, "no due date".to_string()),
+        Some(due_at) if due_at <= now => (1.0, "overdue".to_string()),
+        Some(due_at) => {
+            let days_left = (due_at - now).num_minutes() as f64 / (24.0 * 60.0);
+            (1.0 / (1.0 + days_left), format!("due in {:.1} days", days_left))
+        }
+    }
+}
+
+// 支持 urgent/high/medium/low、p0-p4 和数字 1-5（数字越小优先级越高）
+fn priority_factor(event: &TodoEvent, priority_tag: &str) -> (f64, String) {
+    let Some(value) = event.tags.get(priority_tag) else {
+        return (0.0, format!("no {} tag", priority_tag));
+    };
+    let normalized = value.trim().to_lowercase();
+    let factor = match normalized.as_str() {
+        "urgent" | "critical" | "highest" => Some(1.0),
+        "high" => Some(0.75),
+        "medium" | "normal" => Some(0.5),
+        "low" => Some(0.25),
+        "lowest" | "someday" => Some(0.0),
+        other => {
+            let (digits, offset) = match other.strip_prefix('p') {
+                Some(rest) => (rest, 0.0),
+                None => (other, 1.0),
+            };
+            digits
+                .parse::<f64>()
+                .ok()
+                .map(|level| (1.0 - (level - offset) / 4.0).clamp(0.0, 1.0))
+        }
+    };
+    match factor {
+        Some(factor) => (factor, format!("{} = {}", priority_tag, value)),
+        None => (0.0, format!("unrecognized {} \"{}\"", priority_tag, value)),
+    }
+}
+
+fn unblocks_factor(unblocked: usize) -> (f64, String) {
+    let factor = 1.0 - 1.0 / (1.0 + unblocked as f64);
+    (factor, format!("unblocks {} tasks", unblocked))
+}
+
+// 预估工时越短分数越高（优先完成快速任务），没有预估为 0
+fn effort_factor(event: &TodoEvent) -> (f64, String) {
+    match event.estimate_minutes {
+        None => (0.0, "no estimate".to_string()),
+        Some(minutes) => {
+            let minutes = minutes.max(0) as f64;
+            (1.0 / (1.0 + minutes / 60.0), format!("estimated {} minutes", minutes))
+        }
+    }
+}
+
+// 创建越久分数越高，一周左右达到 0.5
+fn age_factor(event: &TodoEvent, now: DateTime<Utc>) -> (f64, String) {
+    let days = ((now - event.created_at).num_minutes().max(0) as f64) / (24.0 * 60.0);
+    (1.0 - 1.0 / (1.0 + days / 7.0), format!("created {:.1} days ago", days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn event(id: &str, deps: &[&str]) -> TodoEvent {
+        let mut event = TodoEvent::new(id.to_string(), String::new(), HashMap::new(), deps.iter().map(|d| d.to_string()).collect());
+        event.id = id.to_string();
+        event.created_at = now();
+        event.updated_at = now();
+        event
+    }
+
+    fn ids(ranked: &[RankedTask]) -> Vec<&str> {
+        ranked.iter().map(|task| task.event.id.as_str()).collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn applies_each_weight_to_its_factor() {
+        let mut task = event("task", &[]);
+        task.due_at = Some(now() + Duration::days(1));
+        task.tags.insert("level".to_string(), "high".to_string());
+        task.estimate_minutes = Some(60);
+        task.created_at = now() - Duration::days(7);
+        let mut dependent = event("dependent", &["task"]);
+        dependent.status = EventStatus::Blocked;
+
+        let weights = RankingWeights {
+            urgency: 2.0,
+            priority: 4.0,
+            unblocks: 1.0,
+            effort: 3.0,
+            age: 0.0,
+            priority_tag: "level".to_string(),
+        };
+        let ranked = rank_ready_events(&[task, dependent], &weights, now());
+        assert_eq!(ids(&ranked), ["task"]);
+        let breakdown = &ranked[0].breakdown;
+        for (component, factor, weight) in [
+            (&breakdown.urgency, 0.5, 2.0),
+            (&breakdown.priority, 0.75, 4.0),
+            (&breakdown.unblocks, 0.5, 1.0),
+            (&breakdown.effort, 0.5, 3.0),
+            (&breakdown.age, 0.5, 0.0),
+        ] {
+            assert_close(component.factor, factor);
+            assert_close(component.weight, weight);
+            assert_close(component.contribution, factor * weight);
+        }
+        assert_close(ranked[0].score, 1.0 + 3.0 + 0.5 + 1.5);
+    }
+
+    #[test]
+    fn breakdown_sums_to_the_score() {
+        let mut overdue = event("overdue", &[]);
+        overdue.due_at = Some(now() - Duration::hours(1));
+        overdue.tags.insert("priority".to_string(), "p1".to_string());
+        let mut quick = event("quick", &[]);
+        quick.estimate_minutes = Some(15);
+        quick.created_at = now() - Duration::days(30);
+        let mut chained = event("chained", &["quick"]);
+        chained.status = EventStatus::Blocked;
+        let mut last = event("last", &["chained"]);
+        last.status = EventStatus::Blocked;
+
+        let ranked = rank_ready_events(&[overdue, quick, chained, last], &RankingWeights::default(), now());
+        assert_eq!(ranked.len(), 2);
+        for task in &ranked {
+            let b = &task.breakdown;
+            let sum = b.urgency.contribution
+                + b.priority.contribution
+                + b.unblocks.contribution
+                + b.effort.contribution
+                + b.age.contribution;
+            assert_close(task.score, sum);
+        }
+        // 传递地计算被推进的事件
+        let quick = ranked.iter().find(|task| task.event.id == "quick").unwrap();
+        assert_eq!(quick.breakdown.unblocks.reason, "unblocks 2 tasks");
+    }
+
+    #[test]
+    fn equal_scores_rank_older_events_first_then_by_id() {
+        let weights = RankingWeights { age: 0.0, ..RankingWeights::default() };
+        let mut older = event("z-older", &[]);
+        older.created_at = now() - Duration::days(3);
+        let events = vec![event("b", &[]), event("a", &[]), older];
+
+        let ranked = rank_ready_events(&events, &weights, now());
+        assert_eq!(ids(&ranked), ["z-older", "a", "b"]);
+        let reversed: Vec<TodoEvent> = events.into_iter().rev().collect();
+        assert_eq!(ids(&rank_ready_events(&reversed, &weights, now())), ["z-older", "a", "b"]);
+    }
+
+    #[test]
+    fn only_pending_events_that_can_start_are_ranked() {
+        let mut blocked = event("blocked", &[]);
+        blocked.status = EventStatus::Blocked;
+        let mut in_progress = event("in-progress", &[]);
+        in_progress.status = EventStatus::InProgress;
+        let mut completed = event("completed", &[]);
+        completed.status = EventStatus::Completed;
+        let mut deferred = event("deferred", &[]);
+        deferred.start_after = Some(now() + Duration::minutes(1));
+        let mut started = event("started", &[]);
+        started.start_after = Some(now());
+
+        let events = [event("pending", &[]), blocked, in_progress, completed, deferred, started];
+        assert_eq!(ids(&rank_ready_events(&events, &RankingWeights::default(), now())), ["pending", "started"]);
+    }
+}
@@ -1,5 +1,7 @@
 import React, { useState } from 'react';
 import { Button } from '@/components/ui/button';
+import { errorMessage } from '@/lib/utils';
+import { DatabaseInfo } from '@/types';
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
 import { Dialog, DialogContent, DialogHeader, DialogTitle, DialogTrigger } from '@/components/ui/dialog';
 import { Input } from '@/components/ui/input';
@@ -11,7 +13,10 @@ import {
   Plus, 
   FileText,
   AlertCircle,
-  CheckCircle
+  CheckCircle,
+  Pin,
+  PinOff,
+  X
 } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
 import * as dialog from '@tauri-apps/plugin-dialog';
@@ -21,12 +26,11 @@ interface DatabaseManagerProps {
   onDatabaseChange?: (dbPath: string) => void;
 }
 
-interface DatabaseInfo {
-  path: string;
-  name: string;
-  lastModified: string;
-  size: string;
-}
+const formatSize = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+};
 
 export const DatabaseManager: React.FC<DatabaseManagerProps> = ({ 
   trigger, 
@@ -57,7 +61,7 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
       const recent = await invoke<DatabaseInfo[]>('get_recent_databases');
       setRecentDatabases(recent);
     } catch (err) {
-      setError(`Failed to load database info: ${err}`);
+      setError(`Failed to load database info: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
@@ -82,8 +86,8 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
 
       setLoading(true);
       
-      // Create new database
-      await invoke('create_new_database', { path: filePath });
+      // Create new database; the save dialog has already confirmed replacing an existing file
+      await invoke('create_new_database', { path: filePath, overwrite: true });
       
       // Switch to the new database
       await invoke('switch_database', { path: filePath });
@@ -100,7 +104,7 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
       await loadDatabaseInfo();
       
     } catch (err) {
-      setError(`Failed to create database: ${err}`);
+      setError(`Failed to create database: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
@@ -141,13 +145,39 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
       await loadDatabaseInfo();
       
     } catch (err) {
-      setError(`Failed to load database: ${err}`);
+      setError(`Failed to load database: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
   };
 
+  const handleTogglePinned = async (dbInfo: DatabaseInfo) => {
+    try {
+      const recent = await invoke<DatabaseInfo[]>('set_recent_database_pinned', {
+        path: dbInfo.path,
+        pinned: !dbInfo.pinned,
+      });
+      setRecentDatabases(recent);
+    } catch (err) {
+      setError(`Failed to update recent databases: ${errorMessage(err)}`);
+    }
+  };
+
+  const handleRemoveRecentDatabase = async (dbInfo: DatabaseInfo) => {
+    try {
+      const recent = await invoke<DatabaseInfo[]>('remove_recent_database', { path: dbInfo.path });
+      setRecentDatabases(recent);
+    } catch (err) {
+      setError(`Failed to update recent databases: ${errorMessage(err)}`);
+    }
+  };
+
   const handleLoadRecentDatabase = async (dbInfo: DatabaseInfo) => {
+    if (dbInfo.missing) {
+      setError(`Database file not found: ${dbInfo.path}`);
+      return;
+    }
+
     try {
       setError('');
       setSuccess('');
@@ -165,8 +195,11 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
         onDatabaseChange(dbInfo.path);
       }
       
+      // Refresh recent databases
+      await loadDatabaseInfo();
+      
     } catch (err) {
-      setError(`Failed to load database: ${err}`);
+      setError(`Failed to load database: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
@@ -271,22 +304,49 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
                 <CardContent>
                   <ScrollArea className="max-h-48">
                     <div className="space-y-2">
-                      {recentDatabases.map((db, index) => (
+                      {recentDatabases.map((db) => (
                         <div
-                          key={index}
-                          className="flex items-center gap-3 p-3 border rounded-lg hover:bg-gray-50 cursor-pointer"
+                          key={db.path}
+                          className={`flex items-center gap-3 p-3 border rounded-lg ${
+                            db.missing ? 'opacity-60' : 'hover:bg-gray-50 cursor-pointer'
+                          }`}
                           onClick={() => handleLoadRecentDatabase(db)}
                         >
                           <Database className="w-4 h-4 text-gray-500" />
                           <div className="flex-1 min-w-0">
-                            <div className="font-medium text-sm">{db.name}</div>
+                            <div className="font-medium text-sm">
+                              {db.name}
+                              {db.missing && <span className="ml-2 text-xs text-red-600">File not found</span>}
+                            </div>
                             <div className="text-xs text-gray-500 truncate" title={db.path}>
                               {db.path}
                             </div>
                             <div className="text-xs text-gray-400">
-                              Modified: {db.lastModified} • Size: {db.size}
+                              Opened: {new Date(db.last_opened).toLocaleString()} • Size: {formatSize(db.size)}
                             </div>
                           </div>
+                          <Button
+                            variant="ghost"
+                            size="sm"
+                            title={db.pinned ? 'Unpin' : 'Pin'}
+                            onClick={(e) => {
+                              e.stopPropagation();
+                              handleTogglePinned(db);
+                            }}
+                          >
+                            {db.pinned ? <PinOff className="w-4 h-4" /> : <Pin className="w-4 h-4" />}
+                          </Button>
+                          <Button
+                            variant="ghost"
+                            size="sm"
+                            title="Remove from list"
+                            onClick={(e) => {
+                              e.stopPropagation();
+                              handleRemoveRecentDatabase(db);
+                            }}
+                          >
+                            <X className="w-4 h-4" />
+                          </Button>
                         </div>
                       ))}
                     </div>
@@ -304,7 +364,7 @@ export const DatabaseManager: React.FC<DatabaseManagerProps> = ({
                 <div className="text-sm text-gray-600 space-y-2">
                   <p><strong>Create New Database:</strong> Creates a new empty database at the specified location.</p>
                   <p><strong>Load Existing Database:</strong> Opens an existing database file (.db, .sqlite, .sqlite3).</p>
-                  <p><strong>Recent Databases:</strong> Quick access to recently used databases.</p>
+                  <p><strong>Recent Databases:</strong> Quick access to recently used databases. Pinned databases stay at the top.</p>
                   <p className="text-xs text-gray-500 mt-3">
                     Note: Switching databases will reload all events from the selected database.
                   </p>
@@ -1,5 +1,7 @@
 import React, { useState } from 'react';
 import { Button } from '@/components/ui/button';
+import { errorMessage } from '@/lib/utils';
+import { SaveDialogSelection } from '@/types';
 import { Database } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
 
@@ -12,8 +14,8 @@ export const DatabaseManagerSimple: React.FC = () => {
       setLoading(true);
       setMessage('');
       
-      // Use Tauri's built-in dialog commands instead of plugin
-      const filePath = await invoke<string | null>('show_save_dialog', {
+      // Native save dialog; asks before returning an existing file
+      const selection = await invoke<SaveDialogSelection | null>('show_save_dialog', {
         title: 'Create New Database',
         defaultPath: 'todo-database.db',
         filters: [{
@@ -22,18 +24,26 @@ export const DatabaseManagerSimple: React.FC = () => {
         }]
       });
 
-      if (!filePath) {
+      if (!selection) {
         setMessage('No file selected');
         return;
       }
 
-      await invoke('create_new_database', { path: filePath });
-      await invoke('switch_database', { path: filePath });
+      // The dialog did not ask about this file (e.g. after adding the .db extension)
+      const overwrite = selection.overwrite
+        || (selection.exists && window.confirm(`${selection.path} already exists. Replace it?`));
+      if (selection.exists && !overwrite) {
+        setMessage('Cancelled');
+        return;
+      }
+
+      await invoke('create_new_database', { path: selection.path, overwrite });
+      await invoke('switch_database', { path: selection.path });
       
-      setMessage(`Database created successfully: ${filePath}`);
+      setMessage(`Database created successfully: ${selection.path}`);
       
     } catch (err) {
-      setMessage(`Error: ${err}`);
+      setMessage(`Error: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
@@ -44,7 +54,7 @@ export const DatabaseManagerSimple: React.FC = () => {
       setLoading(true);
       setMessage('');
       
-      // Use Tauri's built-in dialog commands instead of plugin
+      // Native open dialog; the backend validates the selected database
       const filePath = await invoke<string | null>('show_open_dialog', {
         title: 'Load Existing Database',
         filters: [{
@@ -58,13 +68,12 @@ export const DatabaseManagerSimple: React.FC = () => {
         return;
       }
 
-      await invoke('validate_database', { path: filePath });
       await invoke('switch_database', { path: filePath });
       
       setMessage(`Database loaded successfully: ${filePath}`);
       
     } catch (err) {
-      setMessage(`Error: ${err}`);
+      setMessage(`Error: ${errorMessage(err)}`);
     } finally {
       setLoading(false);
     }
@@ -1,15 +1,15 @@
 import React, { useState, useEffect, useRef } from 'react';
-import { TodoEvent, EventStatus } from '@/types';
+import { TodoEvent, EventStatus, DeletePolicy } from '@/types';
 import { useEventStore } from '@/store/eventStore';
 import { Button } from '@/components/ui/button';
-import { Calendar, Settings, HelpCircle, Plus } from 'lucide-react';
+import { Calendar, Settings, HelpCircle, Plus, Undo, Redo, Lock, AlertTriangle, X } from 'lucide-react';
 import { EventFormDialog } from './EventFormDialog';
 import { TaskList } from './TaskList';
 import { TagSortManager } from './TagSortManager';
 import { DatabaseManager } from './DatabaseManager';
 
 export const MainLayout: React.FC = () => {
-  const { events, deleteEvent, selectedEvent, setSelectedEvent, fetchEvents, updateEventStatus } = useEventStore();
+  const { events, nextCursor, totalEvents, fetchMoreEvents, deleteEvent, selectedEvent, setSelectedEvent, fetchEvents, updateEventStatus, undo, redo, undoState, fetchUndoState, databaseStatus, fetchDatabaseStatus, startupWarning, fetchStartupWarning, dismissStartupWarning } = useEventStore();
   const [formDialogOpen, setFormDialogOpen] = useState(false);
   const [formMode, setFormMode] = useState<'create' | 'edit'>('create');
   const [editingEvent, setEditingEvent] = useState<TodoEvent | null>(null);
@@ -28,17 +28,52 @@ export const MainLayout: React.FC = () => {
   // Drag and drop state for tasks
   const [draggedEvent, setDraggedEvent] = useState<TodoEvent | null>(null);
   
+  // Tell the user if the last used database could not be reopened
+  useEffect(() => {
+    fetchStartupWarning();
+  }, [fetchStartupWarning]);
+
   // Initialize events loading
   useEffect(() => {
     fetchEvents();
+    fetchDatabaseStatus();
     
     // Set up periodic refresh to ensure UI state sync with backend
     const refreshInterval = setInterval(() => {
       fetchEvents();
+      fetchDatabaseStatus();
     }, 10000); // Refresh every 10 seconds
     
     return () => clearInterval(refreshInterval);
-  }, [fetchEvents]);
+  }, [fetchEvents, fetchDatabaseStatus]);
+
+  // Every mutation replaces the event list, so refresh the undo/redo labels with it
+  useEffect(() => {
+    fetchUndoState();
+  }, [events, fetchUndoState]);
+
+  // Ctrl/Cmd+Z to undo, Ctrl/Cmd+Shift+Z or Ctrl+Y to redo (ignored while typing)
+  useEffect(() => {
+    const handleKeyDown = (e: KeyboardEvent) => {
+      const target = e.target as HTMLElement;
+      if (target.isContentEditable || ['INPUT', 'TEXTAREA', 'SELECT'].includes(target.tagName)) {
+        return;
+      }
+      if (!(e.ctrlKey || e.metaKey)) {
+        return;
+      }
+      const key = e.key.toLowerCase();
+      if (key === 'z' && !e.shiftKey) {
+        e.preventDefault();
+        undo();
+      } else if ((key === 'z' && e.shiftKey) || key === 'y') {
+        e.preventDefault();
+        redo();
+      }
+    };
+    window.addEventListener('keydown', handleKeyDown);
+    return () => window.removeEventListener('keydown', handleKeyDown);
+  }, [undo, redo]);
 
   const handleEventSelect = (event: TodoEvent | null) => {
     setSelectedEvent(event);
@@ -56,8 +91,18 @@ export const MainLayout: React.FC = () => {
     setFormDialogOpen(true);
   };
 
+  // Events that others depend on are only deleted after the user agrees to
+  // remove the dependency from them; the backend refuses anything else
   const handleDeleteEvent = async (event: TodoEvent) => {
-    await deleteEvent(event.id);
+    const dependents = events.filter(other => other.dependencies.includes(event.id));
+    if (dependents.length === 0) {
+      await deleteEvent(event.id, DeletePolicy.Refuse);
+      return;
+    }
+    const names = dependents.map(other => `"${other.name}"`).join(', ');
+    if (window.confirm(`${names} depend on "${event.name}". Remove the dependency and delete it?`)) {
+      await deleteEvent(event.id, DeletePolicy.Detach);
+    }
   };
 
   const handleCompleteEvent = async (event: TodoEvent) => {
@@ -67,6 +112,7 @@ export const MainLayout: React.FC = () => {
   const handleDatabaseChange = async (dbPath: string) => {
     // Reload events from the new database
     await fetchEvents();
+    await fetchDatabaseStatus();
   };
 
   // Handle task drag and drop
@@ -178,6 +224,27 @@ export const MainLayout: React.FC = () => {
                 <Plus className="w-4 h-4 mr-1" />
                 Add Event
               </Button>
+
+              <Button
+                onClick={() => undo()}
+                disabled={!undoState.undo}
+                title={undoState.undo ? `Undo ${undoState.undo}` : 'Nothing to undo'}
+                variant="ghost"
+                size="sm"
+                className="text-white hover:bg-primary-500"
+              >
+                <Undo className="w-4 h-4" />
+              </Button>
+              <Button
+                onClick={() => redo()}
+                disabled={!undoState.redo}
+                title={undoState.redo ? `Redo ${undoState.redo}` : 'Nothing to redo'}
+                variant="ghost"
+                size="sm"
+                className="text-white hover:bg-primary-500"
+              >
+                <Redo className="w-4 h-4" />
+              </Button>
               
               <DatabaseManager 
                 onDatabaseChange={handleDatabaseChange}
@@ -201,6 +268,25 @@ export const MainLayout: React.FC = () => {
         </div>
       </header>
 
+      {startupWarning && (
+        <div className="bg-yellow-100 text-yellow-800 text-sm px-6 py-2 flex items-center gap-2">
+          <AlertTriangle className="w-4 h-4" />
+          <span className="flex-1">{startupWarning}</span>
+          <button onClick={dismissStartupWarning} title="Dismiss">
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+      )}
+
+      {databaseStatus.read_only && (
+        <div className="bg-yellow-100 text-yellow-800 text-sm px-6 py-2 flex items-center gap-2">
+          <Lock className="w-4 h-4" />
+          {databaseStatus.locked_by
+            ? `This database is open in another window (${databaseStatus.locked_by.host}, pid ${databaseStatus.locked_by.pid}). It is read-only until that window closes.`
+            : 'This database is read-only.'}
+        </div>
+      )}
+
       {/* Main Content */}
       <div 
         ref={containerRef}
@@ -297,6 +383,12 @@ export const MainLayout: React.FC = () => {
             )}
           </div>
           <div className="flex items-center space-x-4">
+            <span>Showing {events.length} of {totalEvents} events</span>
+            {nextCursor && (
+              <Button onClick={() => fetchMoreEvents()} variant="ghost" size="sm">
+                Load more
+              </Button>
+            )}
             <span>Todo Scheduler v0.1.0</span>
           </div>
         </div>
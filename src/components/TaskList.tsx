@@ -62,7 +62,7 @@ export const TaskList: React.FC<TaskListProps> = ({
   onTaskDrop,
   draggedEvent,
 }) => {
-  const { events, fetchEvents, getEventDependencies, getSortedEvents } = useEventStore();
+  const { events, fetchEvents, getEventDependencies } = useEventStore();
   const [expandedEvents, setExpandedEvents] = useState<Record<string, boolean>>({});
   const [dependencies, setDependencies] = useState<Record<string, TodoEvent[]>>({});
   const [loading, setLoading] = useState(false);
@@ -177,9 +177,8 @@ export const TaskList: React.FC<TaskListProps> = ({
     };
   }, [draggedEvent, status, isDragOver, onTaskDrop, title]);
 
-  // Filter events by status and apply sorting
-  const baseFilteredEvents = events.filter(event => event.status === status);
-  const filteredEvents = getSortedEvents(baseFilteredEvents);
+  // Filter events by status; sorting is applied by the backend
+  const filteredEvents = events.filter(event => event.status === status);
 
   // Toggle expand/collapse state
   const toggleExpand = async (eventId: string) => {
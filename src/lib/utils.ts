@@ -1,6 +1,16 @@
 import { clsx, type ClassValue } from "clsx"
 import { twMerge } from "tailwind-merge"
+import type { AppError } from "@/types"
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+export function isAppError(error: unknown): error is AppError {
+  return typeof error === "object" && error !== null && "code" in error && "message" in error
+}
+
+// Commands reject with an AppError; anything else is shown as is
+export function errorMessage(error: unknown): string {
+  return isAppError(error) ? error.message : String(error)
+}
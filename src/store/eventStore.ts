@@ -1,15 +1,24 @@
 import { create } from 'zustand';
 import { persist } from 'zustand/middleware';
-import { TodoEvent, EventFilter, CreateEventRequest, UpdateEventRequest, EventStatus, SortPreferences, TagSortRule, DatabaseInfo } from '@/types';
+import { TodoEvent, EventFilter, CreateEventRequest, UpdateEventRequest, EventStatus, SortPreferences, SortKey, SortSpec, TagSortRule, DatabaseInfo, DeletePolicy, DeleteEventResult, UpdateEventResult, Page, SavedView, CreateViewRequest, UpdateViewRequest, HistoryEntry, UndoResult, UndoState, DatabaseStatus } from '@/types';
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '@/lib/utils';
 
 interface EventStore {
   events: TodoEvent[];
+  // Cursor for the next page of the main listing, null when every event is loaded
+  nextCursor: string | null;
+  totalEvents: number;
   selectedEvent: TodoEvent | null;
   filter: EventFilter;
   loading: boolean;
   error: string | null;
   sortPreferences: SortPreferences;
+  views: SavedView[];
+  activeViewId: string | null;
+  undoState: UndoState;
+  databaseStatus: DatabaseStatus;
+  startupWarning: string | null;
   
   // Actions
   setEvents: (events: TodoEvent[]) => void;
@@ -21,30 +30,82 @@ interface EventStore {
   updateTagSortRule: (tagKey: string, direction: 'asc' | 'desc', order: number) => void;
   removeTagSortRule: (tagKey: string) => void;
   reorderTagSortRules: (rules: TagSortRule[]) => void;
-  getSortedEvents: (events: TodoEvent[]) => TodoEvent[];
   
   // API calls
   fetchEvents: () => Promise<void>;
+  fetchMoreEvents: () => Promise<void>;
   createEvent: (request: CreateEventRequest) => Promise<void>;
   updateEvent: (request: UpdateEventRequest) => Promise<void>;
   updateEventStatus: (id: string, status: EventStatus) => Promise<void>;
-  deleteEvent: (id: string) => Promise<void>;
+  // Refuses while other events depend on this one unless the caller picks Detach or Cascade
+  deleteEvent: (id: string, policy?: DeletePolicy) => Promise<void>;
   filterEvents: (filter: EventFilter) => Promise<void>;
+  queryEvents: (query: string) => Promise<void>;
   getEventDependencies: (id: string) => Promise<TodoEvent[]>;
   getEventDependents: (id: string) => Promise<TodoEvent[]>;
+  getEventHistory: (id: string) => Promise<HistoryEntry[]>;
+
+  // Trash
+  listTrash: () => Promise<TodoEvent[]>;
+  restoreEvent: (id: string) => Promise<void>;
+  purgeTrash: () => Promise<void>;
+  getTrashRetentionDays: () => Promise<number>;
+  setTrashRetentionDays: (days: number) => Promise<void>;
+  archiveCompleted: (olderThan?: string) => Promise<void>;
+  getAutoArchiveDays: () => Promise<number | null>;
+  setAutoArchiveDays: (days: number | null) => Promise<void>;
+
+  // Undo/redo (journal is stored in the database)
+  undo: () => Promise<void>;
+  redo: () => Promise<void>;
+  fetchUndoState: () => Promise<void>;
+
+  // Read-only while another app instance is writing to the same file
+  fetchDatabaseStatus: () => Promise<void>;
+
+  // Set when the last used database could not be reopened at launch
+  fetchStartupWarning: () => Promise<void>;
+  dismissStartupWarning: () => void;
+
+  // Saved views
+  fetchViews: () => Promise<void>;
+  createView: (request: CreateViewRequest) => Promise<SavedView>;
+  updateView: (request: UpdateViewRequest) => Promise<void>;
+  deleteView: (id: string) => Promise<void>;
+  applyView: (id: string) => Promise<void>;
   
   // Database management
   getCurrentDatabasePath: () => Promise<string>;
   getRecentDatabases: () => Promise<DatabaseInfo[]>;
-  createNewDatabase: (path: string) => Promise<void>;
+  createNewDatabase: (path: string, overwrite?: boolean) => Promise<void>;
   validateDatabase: (path: string) => Promise<void>;
   switchDatabase: (path: string) => Promise<void>;
 }
 
+// Number of events loaded per page of the main listing
+const PAGE_SIZE = 100;
+
+// Convert the tag sort preferences into a backend sort spec
+const toSortSpec = (preferences: SortPreferences): SortSpec | undefined => {
+  if (!preferences.enabled || preferences.tagSortRules.length === 0) {
+    return undefined;
+  }
+  return {
+    keys: [...preferences.tagSortRules]
+      .sort((a, b) => a.order - b.order)
+      .map((rule): SortKey => ({
+        field: { kind: 'tag', key: rule.tagKey },
+        direction: rule.direction,
+      })),
+  };
+};
+
 export const useEventStore = create<EventStore>()(
   persist(
     (set, get) => ({
       events: [],
+      nextCursor: null,
+      totalEvents: 0,
       selectedEvent: null,
       filter: {},
       loading: false,
@@ -53,13 +114,21 @@ export const useEventStore = create<EventStore>()(
         tagSortRules: [],
         enabled: false,
       },
+      views: [],
+      activeViewId: null,
+      undoState: { undo: null, redo: null },
+      databaseStatus: { read_only: false, locked_by: null },
+      startupWarning: null,
 
       setEvents: (events) => set({ events }),
       setSelectedEvent: (event) => set({ selectedEvent: event }),
       setFilter: (filter) => set({ filter }),
       setLoading: (loading) => set({ loading }),
       setError: (error) => set({ error }),
-      setSortPreferences: (preferences) => set({ sortPreferences: preferences }),
+      setSortPreferences: (preferences) => {
+        set({ sortPreferences: preferences });
+        get().fetchEvents();
+      },
 
       updateTagSortRule: (tagKey, direction, order) => {
         const { sortPreferences } = get();
@@ -82,6 +151,7 @@ export const useEventStore = create<EventStore>()(
             tagSortRules: newRules,
           }
         });
+        get().fetchEvents();
       },
 
       removeTagSortRule: (tagKey) => {
@@ -94,6 +164,7 @@ export const useEventStore = create<EventStore>()(
             tagSortRules: newRules,
           }
         });
+        get().fetchEvents();
       },
 
       reorderTagSortRules: (rules) => {
@@ -104,58 +175,52 @@ export const useEventStore = create<EventStore>()(
             tagSortRules: rules,
           }
         });
+        get().fetchEvents();
       },
 
-      getSortedEvents: (events) => {
-        const { sortPreferences } = get();
-        
-        if (!sortPreferences.enabled || sortPreferences.tagSortRules.length === 0) {
-          return events;
-        }
-
-        return [...events].sort((a, b) => {
-          for (const rule of sortPreferences.tagSortRules) {
-            // Safely access tags with null/undefined check
-            const aTags = a.tags || {};
-            const bTags = b.tags || {};
-            const aValue = aTags[rule.tagKey] || '';
-            const bValue = bTags[rule.tagKey] || '';
-            
-            // Handle numeric values
-            const aNum = parseFloat(aValue);
-            const bNum = parseFloat(bValue);
-            const isNumeric = !isNaN(aNum) && !isNaN(bNum);
-            
-            let comparison = 0;
-            
-            if (isNumeric) {
-              comparison = aNum - bNum;
-            } else {
-              comparison = aValue.localeCompare(bValue);
-            }
-            
-            if (comparison !== 0) {
-              return rule.direction === 'asc' ? comparison : -comparison;
-            }
-          }
-          
-          return 0;
-        });
-      },
-
+  // Reloads the pages already shown, so the periodic refresh never transfers more than that
   fetchEvents: async () => {
     try {
       set({ loading: true, error: null });
-      const events = await invoke<TodoEvent[]>('get_all_events');
+      const filter = { sort: toSortSpec(get().sortPreferences) };
+      const limit = Math.max(PAGE_SIZE, get().events.length);
+      const page = await invoke<Page<TodoEvent>>('filter_events', { filter, limit });
       // Ensure all events have safe tags and dependencies
-      const safeEvents = events.map(event => ({
+      const safeEvents = page.items.map(event => ({
         ...event,
         tags: event.tags || {},
         dependencies: event.dependencies || [],
       }));
-      set({ events: safeEvents, loading: false });
+      set({ events: safeEvents, nextCursor: page.next_cursor, totalEvents: page.total, loading: false });
     } catch (error) {
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
+    }
+  },
+
+  fetchMoreEvents: async () => {
+    const { nextCursor: cursor, sortPreferences } = get();
+    if (!cursor) {
+      return;
+    }
+    try {
+      set({ loading: true, error: null });
+      // The cursor is only valid for the filter and sort it was created with
+      const filter = { sort: toSortSpec(sortPreferences) };
+      const page = await invoke<Page<TodoEvent>>('filter_events', { filter, limit: PAGE_SIZE, cursor });
+      const safeEvents = page.items.map(event => ({
+        ...event,
+        tags: event.tags || {},
+        dependencies: event.dependencies || [],
+      }));
+      const { events } = get();
+      set({
+        events: [...events, ...safeEvents],
+        nextCursor: page.next_cursor,
+        totalEvents: page.total,
+        loading: false,
+      });
+    } catch (error) {
+      set({ error: errorMessage(error), loading: false });
     }
   },
 
@@ -172,7 +237,7 @@ export const useEventStore = create<EventStore>()(
       const { events } = get();
       set({ events: [safeNewEvent, ...events], loading: false });
     } catch (error) {
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
       throw error; // Re-throw to allow caller to handle
     }
   },
@@ -181,35 +246,30 @@ export const useEventStore = create<EventStore>()(
     try {
       set({ loading: true, error: null });
       console.log('🏪 Updating event:', request);
-      const updatedEvent = await invoke<TodoEvent | null>('update_event', { request });
-      console.log('🏪 Update event result:', updatedEvent);
+      const result = await invoke<UpdateEventResult | null>('update_event', { request });
+      console.log('🏪 Update event result:', result);
       
-      if (updatedEvent) {
-        // If dependencies changed, refresh all events to ensure status is up-to-date
-        if (request.dependencies !== undefined) {
-          console.log('🏪 Dependencies changed, refreshing all events');
-          await get().fetchEvents();
-        } else {
-          const { events } = get();
-          // Ensure tags and dependencies are always objects/arrays, never null/undefined
-          const safeUpdatedEvent = {
-            ...updatedEvent,
-            tags: updatedEvent.tags || {},
-            dependencies: updatedEvent.dependencies || [],
-          };
-          const updatedEvents = events.map(event => 
-            event.id === safeUpdatedEvent.id ? safeUpdatedEvent : event
-          );
-          set({ events: updatedEvents, loading: false });
-          console.log('🏪 Events updated successfully');
-        }
+      if (result) {
+        const { events } = get();
+        // Ensure tags and dependencies are always objects/arrays, never null/undefined
+        const safeUpdatedEvent = {
+          ...result.event,
+          tags: result.event.tags || {},
+          dependencies: result.event.dependencies || [],
+        };
+        // Replace the edited event and every dependent whose status cascaded
+        const updatedById = new Map(result.updated.map(event => [event.id, event]));
+        updatedById.set(safeUpdatedEvent.id, safeUpdatedEvent);
+        const updatedEvents = events.map(event => updatedById.get(event.id) ?? event);
+        set({ events: updatedEvents, loading: false });
+        console.log('🏪 Events updated successfully');
       } else {
         console.warn('🏪 Update event returned null, event not found');
         set({ loading: false, error: '事件未找到' });
       }
     } catch (error) {
       console.error('🏪 Update event error:', error);
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
       throw error; // Re-throw to allow caller to handle
     }
   },
@@ -227,7 +287,7 @@ export const useEventStore = create<EventStore>()(
         const { events } = get();
         let newEvents = [...events];
         
-        // 2. 更新所有受影响的事件
+        // 2. 更新所有受影响的事件（后端已沿依赖链完成级联）
         updatedEvents.forEach(updatedEvent => {
           const index = newEvents.findIndex(e => e.id === updatedEvent.id);
           if (index !== -1) {
@@ -235,93 +295,253 @@ export const useEventStore = create<EventStore>()(
           }
         });
 
-        // 3. 如果事件状态变为Completed，检查是否有阻塞事件可以解除
-        if (status === EventStatus.Completed) {
-          // 获取所有依赖此事件的其他事件
-          const dependents = await invoke<TodoEvent[]>('get_event_dependents', { id });
-          console.log('🏪 Found dependents:', dependents);
-          
-          // 检查每个依赖事件是否满足解除阻塞条件
-          for (const dependent of dependents) {
-            if (dependent.status === EventStatus.Blocked) {
-              // 获取该事件的所有依赖项
-              const deps = await invoke<TodoEvent[]>('get_event_dependencies', { id: dependent.id });
-              const allDepsCompleted = deps.every(dep => dep.status === EventStatus.Completed);
-              
-              if (allDepsCompleted) {
-                // 所有依赖项已完成，将事件状态更新为Pending
-                console.log('🏪 Unblocking dependent event:', dependent.id);
-                const unblockedEvents = await invoke<TodoEvent[]>('update_event_status', { 
-                  id: dependent.id, 
-                  status: EventStatus.Pending 
-                });
-                
-                // 更新UI中的事件状态
-                unblockedEvents.forEach(unblockedEvent => {
-                  const idx = newEvents.findIndex(e => e.id === unblockedEvent.id);
-                  if (idx !== -1) {
-                    newEvents[idx] = unblockedEvent;
-                  }
-                });
-              }
-            }
-          }
-        }
-        
         set({ events: newEvents, loading: false });
         console.log('🏪 Events updated after status change');
       }
     } catch (error) {
       console.error('🏪 Update event status error:', error);
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
     }
   },
 
-  deleteEvent: async (id) => {
-    console.log('🏪 Store deleteEvent called with ID:', id);
+  deleteEvent: async (id, policy = DeletePolicy.Refuse) => {
+    console.log('🏪 Store deleteEvent called with ID:', id, policy);
     try {
-      console.log('🏪 Setting loading state...');
       set({ loading: true, error: null });
       
-      console.log('🏪 Invoking Tauri delete_event command...');
-      const success = await invoke<boolean>('delete_event', { id });
-      console.log('🏪 Tauri delete_event result:', success);
+      const result = await invoke<DeleteEventResult>('delete_event', { id, policy });
+      console.log('🏪 Tauri delete_event result:', result);
       
-      if (success) {
+      if (result.deleted.length > 0) {
         const { events, selectedEvent } = get();
-        console.log('🏪 Current events count:', events.length);
-        console.log('🏪 Current selected event:', selectedEvent?.id);
+        const deletedIds = new Set(result.deleted);
+        const updatedById = new Map(result.updated.map(event => [event.id, event]));
         
-        const filteredEvents = events.filter(event => event.id !== id);
-        console.log('🏪 Filtered events count:', filteredEvents.length);
+        // 移除被删除的事件，并替换状态被重新计算的依赖事件
+        const newEvents = events
+          .filter(event => !deletedIds.has(event.id))
+          .map(event => updatedById.get(event.id) ?? event);
         
         // 如果删除的是当前选中的事件，清除选择
-        const newSelectedEvent = selectedEvent?.id === id ? null : selectedEvent;
-        console.log('🏪 New selected event:', newSelectedEvent?.id);
+        const newSelectedEvent = selectedEvent && deletedIds.has(selectedEvent.id) ? null : selectedEvent;
         
         set({ 
-          events: filteredEvents, 
+          events: newEvents, 
           selectedEvent: newSelectedEvent,
           loading: false 
         });
         console.log('🏪 Store state updated successfully');
       } else {
-        console.log('🏪 Delete failed - success is false');
+        console.log('🏪 Delete failed - event not found');
         set({ error: '删除事件失败', loading: false });
       }
     } catch (error) {
       console.error('🏪 Delete event error:', error);
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
     }
   },
 
   filterEvents: async (filter) => {
     try {
       set({ loading: true, error: null, filter });
-      const events = await invoke<TodoEvent[]>('filter_events', { filter });
-      set({ events, loading: false });
+      const { items: events, total } = await invoke<Page<TodoEvent>>('filter_events', { filter });
+      set({ events, nextCursor: null, totalEvents: total, loading: false });
+    } catch (error) {
+      set({ error: errorMessage(error), loading: false });
+    }
+  },
+
+  queryEvents: async (query) => {
+    try {
+      set({ loading: true, error: null, filter: { query } });
+      const { items: events, total } = await invoke<Page<TodoEvent>>('query_events', { query });
+      set({ events, nextCursor: null, totalEvents: total, loading: false });
+    } catch (error) {
+      set({ error: errorMessage(error), loading: false });
+    }
+  },
+
+  listTrash: async () => {
+    try {
+      return await invoke<TodoEvent[]>('list_trash');
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      return [];
+    }
+  },
+
+  restoreEvent: async (id) => {
+    try {
+      set({ error: null });
+      await invoke<TodoEvent[]>('restore_event', { id });
+      // Restoring can re-block dependents, so reload everything
+      await get().fetchEvents();
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  purgeTrash: async () => {
+    try {
+      const purged = await invoke<string[]>('purge_trash');
+      console.log('🗑️ Purged events from trash:', purged.length);
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  getTrashRetentionDays: async () => {
+    return await invoke<number>('get_trash_retention_days');
+  },
+
+  setTrashRetentionDays: async (days) => {
+    try {
+      await invoke('set_trash_retention_days', { days });
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  archiveCompleted: async (olderThan) => {
+    try {
+      set({ error: null });
+      const archived = await invoke<string[]>('archive_completed', { olderThan: olderThan ?? null });
+      console.log('📦 Archived completed events:', archived.length);
+      await get().fetchEvents();
+      await get().fetchUndoState();
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  getAutoArchiveDays: async () => {
+    return await invoke<number | null>('get_auto_archive_days');
+  },
+
+  setAutoArchiveDays: async (days) => {
+    try {
+      await invoke('set_auto_archive_days', { days });
     } catch (error) {
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  undo: async () => {
+    try {
+      set({ error: null });
+      const result = await invoke<UndoResult | null>('undo');
+      if (result) {
+        console.log('↩️ Undid:', result.label);
+      }
+      await get().fetchEvents();
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  redo: async () => {
+    try {
+      set({ error: null });
+      const result = await invoke<UndoResult | null>('redo');
+      if (result) {
+        console.log('↪️ Redid:', result.label);
+      }
+      await get().fetchEvents();
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  fetchUndoState: async () => {
+    try {
+      const undoState = await invoke<UndoState>('get_undo_state');
+      set({ undoState });
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  fetchDatabaseStatus: async () => {
+    try {
+      const databaseStatus = await invoke<DatabaseStatus>('get_database_status');
+      set({ databaseStatus });
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  fetchStartupWarning: async () => {
+    try {
+      const startupWarning = await invoke<string | null>('take_startup_warning');
+      if (startupWarning) {
+        set({ startupWarning });
+      }
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  dismissStartupWarning: () => set({ startupWarning: null }),
+
+  fetchViews: async () => {
+    try {
+      const views = await invoke<SavedView[]>('get_views');
+      set({ views });
+    } catch (error) {
+      set({ error: errorMessage(error) });
+    }
+  },
+
+  createView: async (request) => {
+    try {
+      const view = await invoke<SavedView>('create_view', { request });
+      await get().fetchViews();
+      return view;
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  updateView: async (request) => {
+    try {
+      await invoke<SavedView | null>('update_view', { request });
+      await get().fetchViews();
+      if (get().activeViewId === request.id) {
+        await get().applyView(request.id);
+      }
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  deleteView: async (id) => {
+    try {
+      await invoke<boolean>('delete_view', { id });
+      if (get().activeViewId === id) {
+        set({ activeViewId: null });
+      }
+      await get().fetchViews();
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      throw error;
+    }
+  },
+
+  applyView: async (id) => {
+    try {
+      set({ loading: true, error: null });
+      const { items: events, total } = await invoke<Page<TodoEvent>>('execute_view', { id });
+      const view = get().views.find(view => view.id === id);
+      set({ events, nextCursor: null, totalEvents: total, loading: false, activeViewId: id, filter: view?.filter ?? {} });
+    } catch (error) {
+      set({ error: errorMessage(error), loading: false });
     }
   },
 
@@ -330,7 +550,7 @@ export const useEventStore = create<EventStore>()(
       const dependencies = await invoke<TodoEvent[]>('get_event_dependencies', { id });
       return dependencies;
     } catch (error) {
-      set({ error: error as string });
+      set({ error: errorMessage(error) });
       return [];
     }
   },
@@ -340,18 +560,28 @@ export const useEventStore = create<EventStore>()(
       const dependents = await invoke<TodoEvent[]>('get_event_dependents', { id });
       return dependents;
     } catch (error) {
-      set({ error: error as string });
+      set({ error: errorMessage(error) });
       return [];
     }
   },
 
+  getEventHistory: async (id) => {
+    try {
+      return await invoke<HistoryEntry[]>('get_event_history', { id });
+    } catch (error) {
+      set({ error: errorMessage(error) });
+      return [];
+    }
+  },
+
+
   // Database management methods
   getCurrentDatabasePath: async () => {
     try {
       const path = await invoke<string>('get_current_database_path');
       return path;
     } catch (error) {
-      set({ error: error as string });
+      set({ error: errorMessage(error) });
       throw error;
     }
   },
@@ -361,18 +591,18 @@ export const useEventStore = create<EventStore>()(
       const databases = await invoke<DatabaseInfo[]>('get_recent_databases');
       return databases;
     } catch (error) {
-      set({ error: error as string });
+      set({ error: errorMessage(error) });
       return [];
     }
   },
 
-  createNewDatabase: async (path) => {
+  createNewDatabase: async (path, overwrite = false) => {
     try {
       set({ loading: true, error: null });
-      await invoke('create_new_database', { path });
+      await invoke('create_new_database', { path, overwrite });
       set({ loading: false });
     } catch (error) {
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
       throw error;
     }
   },
@@ -381,7 +611,7 @@ export const useEventStore = create<EventStore>()(
     try {
       await invoke('validate_database', { path });
     } catch (error) {
-      set({ error: error as string });
+      set({ error: errorMessage(error) });
       throw error;
     }
   },
@@ -390,11 +620,12 @@ export const useEventStore = create<EventStore>()(
     try {
       set({ loading: true, error: null });
       await invoke('switch_database', { path });
-      // After switching database, reload events
-      const events = await invoke<TodoEvent[]>('get_all_events');
-      set({ events, loading: false, selectedEvent: null });
+      // After switching database, reload the first page of events and the views saved in that file
+      set({ events: [], nextCursor: null, selectedEvent: null, activeViewId: null });
+      await get().fetchEvents();
+      await get().fetchViews();
     } catch (error) {
-      set({ error: error as string, loading: false });
+      set({ error: errorMessage(error), loading: false });
       throw error;
     }
   },
@@ -7,6 +7,12 @@ export interface TodoEvent {
   created_at: string;
   updated_at: string;
   dependencies: string[];
+  due_at: string | null;
+  start_after: string | null;
+  completed_at: string | null;
+  estimate_minutes: number | null;
+  deleted_at: string | null; // Set while the event is in the trash
+  archived_at: string | null; // Set once a completed event is archived
 }
 
 export enum EventStatus {
@@ -16,11 +22,31 @@ export enum EventStatus {
   Blocked = 'Blocked',
 }
 
+export enum DeletePolicy {
+  Refuse = 'Refuse',
+  Detach = 'Detach',
+  Cascade = 'Cascade',
+}
+
+export interface DeleteEventResult {
+  deleted: string[];
+  updated: TodoEvent[];
+}
+
+// The edited event plus dependents whose status changed as a result
+export interface UpdateEventResult {
+  event: TodoEvent;
+  updated: TodoEvent[];
+}
+
 export interface CreateEventRequest {
   name: string;
   description: string;
   tags: Record<string, string>;
   dependencies: string[];
+  due_at?: string | null;
+  start_after?: string | null;
+  estimate_minutes?: number | null;
 }
 
 export interface UpdateEventRequest {
@@ -30,12 +56,174 @@ export interface UpdateEventRequest {
   tags?: Record<string, string>;
   status?: EventStatus;
   dependencies?: string[];
+  // Omit to keep the current value, null to clear it
+  due_at?: string | null;
+  start_after?: string | null;
+  estimate_minutes?: number | null;
 }
 
+export type TagPredicate =
+  | { op: 'exists'; key: string }
+  | { op: 'missing'; key: string }
+  | { op: 'in'; key: string; values: string[] }
+  | { op: 'prefix'; key: string; prefix: string }
+  | { op: 'range'; key: string; min?: number; max?: number };
+
 export interface EventFilter {
   status?: EventStatus;
   tags?: Record<string, string>;
   search?: string;
+  tag_predicates?: TagPredicate[];
+  due_before?: string;
+  due_after?: string;
+  deferred?: boolean;
+  // Archived events are hidden unless this is set
+  include_archived?: boolean;
+  // Query language, e.g. `status:ready tag:area=work due<7d -tag:someday "api"`
+  query?: string;
+  sort?: SortSpec;
+}
+
+export type SortField =
+  | { kind: 'tag'; key: string }
+  | { kind: 'name' }
+  | { kind: 'created' }
+  | { kind: 'updated' }
+  | { kind: 'due' }
+  | { kind: 'status' };
+
+export interface SortKey {
+  field: SortField;
+  direction: 'asc' | 'desc';
+}
+
+export interface SortSpec {
+  keys: SortKey[];
+}
+
+// Named filter stored in the database file
+export interface SavedView {
+  id: string;
+  name: string;
+  filter: EventFilter;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface CreateViewRequest {
+  name: string;
+  filter: EventFilter;
+}
+
+export interface UpdateViewRequest {
+  id: string;
+  name?: string;
+  filter?: EventFilter;
+}
+
+export type ChangeCause = 'User' | 'Cascade' | 'Import' | 'Rule';
+
+export type HistoryAction = 'Created' | 'Updated' | 'Deleted' | 'Restored' | 'Purged';
+
+// old/new are JSON values; null means the field was empty or absent
+export interface FieldChange {
+  field: string;
+  old: unknown;
+  new: unknown;
+}
+
+export interface HistoryEntry {
+  id: number;
+  event_id: string;
+  action: HistoryAction;
+  cause: ChangeCause;
+  changes: FieldChange[];
+  recorded_at: string;
+}
+
+export interface UndoResult {
+  label: string;
+  restored: TodoEvent[];
+  deleted: string[];
+}
+
+// Labels of the operations that undo/redo would apply, null when unavailable
+export interface UndoState {
+  undo: string | null;
+  redo: string | null;
+}
+
+export type ErrorCode = 'NotFound' | 'Validation' | 'Conflict' | 'Io' | 'Sqlite' | 'Schema' | 'Internal';
+
+// Rejection value of every command. details depends on the code, e.g.
+// { field, position } for Validation or { ids } for Conflict
+export interface AppError {
+  code: ErrorCode;
+  message: string;
+  details: Record<string, unknown>;
+}
+
+// next_cursor is opaque; pass it back unchanged to fetch the following page
+export interface Page<T> {
+  items: T[];
+  next_cursor: string | null;
+  total: number;
+}
+
+// Times are minutes relative to the project start
+export interface ScheduleEntry {
+  event_id: string;
+  name: string;
+  duration_minutes: number;
+  has_estimate: boolean;
+  earliest_start: number;
+  earliest_finish: number;
+  latest_start: number;
+  latest_finish: number;
+  total_slack: number;
+  critical: boolean;
+}
+
+export interface CriticalPathReport {
+  entries: ScheduleEntry[];
+  critical_path: string[];
+  project_duration_minutes: number;
+}
+
+// Highlighted parts are wrapped in <mark></mark>
+export interface SearchResult {
+  event: TodoEvent;
+  name_highlight: string;
+  snippet: string;
+  score: number;
+}
+
+export interface RankingWeights {
+  urgency: number;
+  priority: number;
+  unblocks: number;
+  effort: number;
+  age: number;
+  priority_tag: string;
+}
+
+export interface ScoreComponent {
+  factor: number;
+  weight: number;
+  contribution: number;
+  reason: string;
+}
+
+export interface RankedTask {
+  event: TodoEvent;
+  score: number;
+  breakdown: {
+    urgency: ScoreComponent;
+    priority: ScoreComponent;
+    unblocks: ScoreComponent;
+    effort: ScoreComponent;
+    age: ScoreComponent;
+  };
 }
 
 export interface EventNode {
@@ -68,9 +256,35 @@ export interface SortPreferences {
   enabled: boolean;
 }
 
+// Entry of the recent databases list; missing is set when the file is gone
 export interface DatabaseInfo {
   path: string;
   name: string;
-  last_modified: string;
-  size: string;
-}
\ No newline at end of file
+  last_opened: string;
+  last_modified: string | null;
+  size: number; // bytes
+  pinned: boolean;
+  missing: boolean;
+}
+
+// The app instance currently allowed to write to the database file
+export interface InstanceLock {
+  instance_id: string;
+  host: string;
+  pid: number;
+  heartbeat_at: string;
+}
+
+// Location picked in the save dialog; overwrite is set when the user confirmed replacing an existing file.
+// exists without overwrite means the user has not been asked yet
+export interface SaveDialogSelection {
+  path: string;
+  overwrite: boolean;
+  exists: boolean;
+}
+
+// read_only is set while another instance holds the lock (locked_by)
+export interface DatabaseStatus {
+  read_only: boolean;
+  locked_by: InstanceLock | null;
+}